@@ -0,0 +1,79 @@
+//! Compares the zero-copy lexer and the interner against the previous
+//! approach of allocating a `String` for every identifier and litteral.
+//!
+//! Run with `cargo bench --bench lexing [users]`.
+
+use std::{collections::HashMap, env, fmt::Write, hint::black_box, time::Instant};
+
+use non::{interner::Interner, lexer::NonLexer, parser::NonParser, token::TokenKind};
+
+fn generate(users: usize) -> String {
+    let mut source = String::new();
+
+    source.push_str("univ:\n.name 'Université Exemple'\n.domain 'exemple.tld'\n\n");
+    source.push_str("student:\n.name .login\n.mail .login '.etu@' univ.domain\n.login @\n\n");
+
+    for user in 0..users {
+        writeln!(source, "user{}: student", user).unwrap();
+        writeln!(source, ".login 'user{}'", user).unwrap();
+        writeln!(source, ".name 'User {}'\n", user).unwrap();
+    }
+
+    source
+}
+
+fn main() {
+    let users = env::args()
+        .skip(1)
+        .find(|arg| arg != "--bench")
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(20_000);
+
+    let source = generate(users);
+    println!("{} users, {} bytes", users, source.len());
+
+    let start = Instant::now();
+    let tokens = NonLexer::new(&source).count();
+    println!(
+        "borrowed tokens:  {:?} ({} tokens)",
+        start.elapsed(),
+        tokens
+    );
+
+    let start = Instant::now();
+    for token in NonLexer::new(&source) {
+        black_box(token.value.map(str::to_string));
+    }
+    println!("owned tokens:     {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut interner = Interner::new();
+    for token in NonLexer::new(&source) {
+        if token.kind == TokenKind::Identifier {
+            black_box(interner.intern(token.value.unwrap()));
+        }
+    }
+    println!(
+        "interned names:   {:?} ({} distinct)",
+        start.elapsed(),
+        interner.len()
+    );
+
+    let start = Instant::now();
+    let mut names = HashMap::<String, usize>::new();
+    for token in NonLexer::new(&source) {
+        if token.kind == TokenKind::Identifier {
+            *names.entry(token.value.unwrap().to_string()).or_default() += 1;
+        }
+    }
+    println!("string keys:      {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut parser = NonParser::new(NonLexer::new(&source));
+    parser.parse().unwrap();
+    println!(
+        "full parse:       {:?} ({} nons)",
+        start.elapsed(),
+        parser.nons.len()
+    );
+}
@@ -0,0 +1,151 @@
+//! Resolves every field of a large generated definition set, first without
+//! any memoisation as resolution worked before the cache, then with an empty
+//! resolution cache, then once it is warm, and times a full flat export.
+//!
+//! Run with `cargo bench --bench resolution [users] [depth]`.
+
+use std::{collections::HashMap, env, fmt::Write, hint::black_box, time::Instant};
+
+use non::{
+    args::OutputFormat,
+    interner::Symbol,
+    lexer::NonLexer,
+    nds::NonDefs,
+    non::{FieldValue, NonId},
+    parser::NonParser,
+};
+
+fn generate(users: usize, depth: usize) -> String {
+    let mut source = String::new();
+
+    source.push_str("univ:\n.name 'Université Exemple'\n.domain 'exemple.tld'\n\n");
+    source.push_str("level0:\n.login @\n.mail .login '@' univ.domain\n\n");
+
+    for level in 1..depth {
+        writeln!(source, "level{}: level{}", level, level - 1).unwrap();
+        writeln!(source, ".field{} .mail '/' .login", level).unwrap();
+        writeln!(source, ".tag{} 'level{}'\n", level, level).unwrap();
+    }
+
+    for user in 0..users {
+        writeln!(source, "user{}: level{}", user, depth - 1).unwrap();
+        writeln!(source, ".name 'User {}'\n", user).unwrap();
+    }
+
+    source
+}
+
+/// Resolves `field` of the non at `index` the way `get` did before the
+/// cache: every lookup rebuilds the whole inherited field map, cloning the
+/// map of each parent, and references are resolved again on every call.
+fn resolve_uncached(defs: &NonDefs, index: NonId, field: Symbol) -> String {
+    let fields = inherited_fields(defs, index);
+    resolve_value(defs, index, fields[&field].clone())
+}
+
+fn inherited_fields(defs: &NonDefs, index: NonId) -> HashMap<Symbol, FieldValue> {
+    let non = defs.non(index);
+    let mut map = HashMap::new();
+
+    for &parent in &non.parents {
+        map.extend(inherited_fields(defs, parent));
+    }
+
+    map.extend(non.own_fields().clone());
+    map
+}
+
+fn resolve_value(defs: &NonDefs, index: NonId, value: FieldValue) -> String {
+    match value {
+        FieldValue::Litteral(value) => value,
+        FieldValue::Vec(values) => values
+            .into_iter()
+            .map(|value| resolve_value(defs, index, value))
+            .collect(),
+        FieldValue::FieldReference(field) if field == Symbol::ID => defs.id_of(index).to_string(),
+        FieldValue::FieldReference(field) => resolve_uncached(defs, index, field),
+        FieldValue::ObjRef(other, field) => resolve_uncached(defs, other, field),
+        _ => unreachable!("not generated by this benchmark"),
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1).filter(|arg| arg != "--bench");
+    let users = args.next().and_then(|a| a.parse().ok()).unwrap_or(2_000);
+    let depth = args.next().and_then(|a| a.parse().ok()).unwrap_or(8);
+
+    let source = generate(users, depth);
+    let mut parser = NonParser::new(NonLexer::new(&source));
+    parser.parse().unwrap();
+
+    let non_defs = NonDefs::builder()
+        .format(OutputFormat::Json)
+        .nons(parser.nons)
+        .interner(parser.interner)
+        .flat(true)
+        .build()
+        .unwrap();
+
+    let ids = (0..users)
+        .map(|user| format!("user{}", user))
+        .collect::<Vec<_>>();
+    let fields = non_defs
+        .fields(&ids[0])
+        .unwrap()
+        .keys()
+        .map(|&field| non_defs.name(field))
+        .collect::<Vec<_>>();
+
+    println!(
+        "{} users, depth {}, {} fields each",
+        users,
+        depth,
+        fields.len()
+    );
+
+    let indices = ids
+        .iter()
+        .map(|id| non_defs.index_of(id).unwrap())
+        .collect::<Vec<_>>();
+    let symbols = fields
+        .iter()
+        .map(|field| non_defs.interner().get(field).unwrap())
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    for &index in &indices {
+        for &field in &symbols {
+            black_box(resolve_uncached(&non_defs, index, field));
+        }
+    }
+    println!("no cache: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    for id in &ids {
+        for field in &fields {
+            black_box(non_defs.get(id, field).unwrap());
+        }
+    }
+    println!("cold cache: {:?}", start.elapsed());
+
+    for (id, &index) in ids.iter().zip(&indices) {
+        for (field, &symbol) in fields.iter().zip(&symbols) {
+            assert_eq!(
+                resolve_uncached(&non_defs, index, symbol),
+                non_defs.get(id, field).unwrap()
+            );
+        }
+    }
+
+    let start = Instant::now();
+    for id in &ids {
+        for field in &fields {
+            black_box(non_defs.get(id, field).unwrap());
+        }
+    }
+    println!("warm cache: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    black_box(non_defs.serialize().unwrap());
+    println!("flat json export: {:?}", start.elapsed());
+}
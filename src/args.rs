@@ -1,4 +1,6 @@
-use clap::{Parser, ValueEnum};
+use std::fmt::Display;
+
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 #[clap(rename_all = "kebab_case")]
@@ -6,22 +8,82 @@ pub enum OutputFormat {
     Json,
     Yaml,
     Non,
+    Toml,
+    Ldif,
+    Csv,
+    Tsv,
+    JsonSchema,
+    Typescript,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Json => f.write_str("json"),
+            OutputFormat::Yaml => f.write_str("yaml"),
+            OutputFormat::Non => f.write_str("non"),
+            OutputFormat::Toml => f.write_str("toml"),
+            OutputFormat::Ldif => f.write_str("ldif"),
+            OutputFormat::Csv => f.write_str("csv"),
+            OutputFormat::Tsv => f.write_str("tsv"),
+            OutputFormat::JsonSchema => f.write_str("json-schema"),
+            OutputFormat::Typescript => f.write_str("typescript"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum GraphFormat {
+    #[default]
+    Dot,
+    Mermaid,
 }
 
-impl ToString for OutputFormat {
-    fn to_string(&self) -> String {
+impl Display for GraphFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            OutputFormat::Json => "json",
-            OutputFormat::Yaml => "yaml",
-            OutputFormat::Non => "non",
-        }.to_string()
+            GraphFormat::Dot => f.write_str("dot"),
+            GraphFormat::Mermaid => f.write_str("mermaid"),
+        }
     }
 }
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long)]
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Convert a .non file to another format
+    Convert(ConvertArgs),
+
+    /// Validate every concrete non against the schemas it inherits
+    Check(CheckArgs),
+
+    /// Generate one non per row of a CSV file, all inheriting from a template
+    ImportCsv(ImportCsvArgs),
+
+    /// Compare the nons of two files, field by field, failing when they differ
+    Diff(DiffArgs),
+
+    /// Rewrite the definitions of a file without changing what they resolve to
+    Refactor(RefactorArgs),
+
+    /// Generate types for the fields of nons, as the flat JSON export writes them
+    Codegen(CodegenArgs),
+
+    /// Draw the inheritance and references between nons
+    Graph(GraphArgs),
+
+    /// Report definitions that are likely mistakes or break conventions
+    Lint(LintArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ConvertArgs {
     pub path: String,
 
     #[arg(short, long, default_value_t = OutputFormat::Non)]
@@ -30,6 +92,216 @@ pub struct Args {
     #[arg(short, long)]
     pub output: Option<String>,
 
-    #[arg(long, default_value_t=false)]
+    #[arg(long, default_value_t = false)]
     pub flat: bool,
+
+    /// .non file applied on top of the input, can be repeated
+    #[arg(long = "overlay")]
+    pub overlays: Vec<String>,
+
+    /// Override a field before resolution, as `NON.FIELD=VALUE` with VALUE
+    /// written like in a .non file, can be repeated
+    #[arg(long = "set", value_parser = parse_assignment)]
+    pub sets: Vec<(String, String, String)>,
+
+    /// Let overlays and --set add nons and fields missing from the input
+    #[arg(long, default_value_t = false)]
+    pub allow_new: bool,
+
+    /// Read JSON and YAML values as `.non` source, as written by non-flat exports
+    #[arg(long, default_value_t = false)]
+    pub raw: bool,
+
+    /// DN template of LDIF entries, e.g. `uid={login},ou=people,dc=exemple,dc=tld`
+    #[arg(long, default_value = "cn={id}")]
+    pub dn: String,
+
+    /// Object classes of the descendants of a non in LDIF, as `NON=CLASS[,CLASS...]`
+    #[arg(long = "object-class", value_parser = parse_object_class)]
+    pub object_classes: Vec<(String, Vec<String>)>,
+
+    /// Columns of CSV and TSV rows, defaults to every resolved field that is not hidden
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+
+    /// Only write the descendants of this non in CSV and TSV
+    #[arg(long)]
+    pub descendants_of: Option<String>,
+
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+fn parse_object_class(s: &str) -> Result<(String, Vec<String>), String> {
+    let (non, classes) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NON=CLASS[,CLASS...], found '{}'", s))?;
+    let classes = classes.split(',').map(str::to_string).collect();
+    Ok((non.to_string(), classes))
+}
+
+fn parse_assignment(s: &str) -> Result<(String, String, String), String> {
+    let (target, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NON.FIELD=VALUE, found '{}'", s))?;
+    let (non, field) = target
+        .split_once('.')
+        .ok_or_else(|| format!("expected NON.FIELD=VALUE, found '{}'", s))?;
+    Ok((non.to_string(), field.to_string(), value.to_string()))
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    pub path: String,
+
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ImportCsvArgs {
+    /// CSV file whose first row names the columns
+    pub path: String,
+
+    /// Non every generated non inherits from
+    #[arg(long)]
+    pub parent: String,
+
+    /// Column holding the id of each generated non
+    #[arg(long, default_value = "id")]
+    pub id_column: String,
+
+    #[arg(long, default_value_t = ',')]
+    pub delimiter: char,
+
+    /// .non file defining the parent, generated nons are added to it in memory
+    #[arg(long)]
+    pub defs: Option<String>,
+
+    #[arg(short, long, requires = "defs")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(long, default_value_t = false, requires = "defs")]
+    pub flat: bool,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    pub old: String,
+
+    pub new: String,
+
+    /// Compare the values as written instead of resolved
+    #[arg(long, default_value_t = false)]
+    pub raw: bool,
+
+    /// Report the differences as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SandboxArgs {
+    /// Fail on env('...') values instead of reading the environment
+    #[arg(long, default_value_t = false)]
+    pub deny_env: bool,
+
+    /// Fail on file('...') values instead of reading files, relative paths
+    /// being read from the directory of the .non file
+    #[arg(long, default_value_t = false)]
+    pub deny_file: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RefactorArgs {
+    #[command(subcommand)]
+    pub command: RefactorCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RefactorCommand {
+    /// Move the fields shared by a set of nons into a new parent
+    ExtractParent(ExtractParentArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ExtractParentArgs {
+    pub path: String,
+
+    /// Id of the new parent
+    #[arg(long)]
+    pub name: String,
+
+    /// Nons whose common fields are extracted
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub nons: Vec<String>,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CodegenArgs {
+    #[command(subcommand)]
+    pub command: CodegenCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CodegenCommand {
+    /// Generate one serde struct per non
+    Rust(RustArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RustArgs {
+    pub path: String,
+
+    /// Nons to generate a struct for, every non inherited from by default
+    #[arg(long, value_delimiter = ',')]
+    pub nons: Vec<String>,
+
+    /// Give every struct all of its fields instead of composing it from the
+    /// structs of its parents
+    #[arg(long, default_value_t = false)]
+    pub flat: bool,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct GraphArgs {
+    pub path: String,
+
+    #[arg(short, long, default_value_t = GraphFormat::Dot)]
+    pub format: GraphFormat,
+
+    /// Only draw this non and its ancestors
+    #[arg(long)]
+    pub ancestors_of: Option<String>,
+
+    /// Only draw this non and its descendants
+    #[arg(long)]
+    pub descendants_of: Option<String>,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct LintArgs {
+    pub path: String,
+
+    /// YAML or JSON file setting the severity of each rule
+    #[arg(long)]
+    pub config: Option<String>,
 }
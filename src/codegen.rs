@@ -0,0 +1,344 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    error::{NonError, Result},
+    nds::NonDefs,
+    non::NonId,
+    schema::ValueType,
+};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Keywords that cannot be written as raw identifiers either.
+const RUST_RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+/// A field of a generated type, as exported by the flat `json` format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TypeField {
+    pub value_type: Option<ValueType>,
+    /// Whether every descendant exports the field, which is the case when
+    /// the non has a value for it or its schema requires it.
+    pub required: bool,
+    /// Whether the non and every descendant have the same value for the
+    /// field.
+    pub shared: bool,
+}
+
+/// Generates a Rust module with one `serde` struct per non of `nons`,
+/// deserializing the `fields` object the flat `json` export writes for it
+/// and for its descendants.
+///
+/// Struct fields are typed after the schema or the value of their non
+/// field, the same way as in the `json` export: a field whose value has
+/// another type in some descendant is a `String`. Fields are optional when
+/// no value is required. When `flat` is false,
+/// inherited fields are not repeated: each parent becomes a
+/// `#[serde(flatten)]` field of its own struct, generated as well. This
+/// fails when a non does not export the fields of a parent the same way, for
+/// instance when it unsets one, or when two parents export the same field.
+pub fn rust(defs: &NonDefs, nons: &[NonId], flat: bool) -> Result<String> {
+    let mut indices = nons.to_vec();
+    if !flat {
+        indices.extend(nons.iter().flat_map(|&index| defs.ancestors(index)));
+    }
+    indices.sort_unstable();
+    indices.dedup();
+
+    let type_fields = type_fields(defs);
+    let mut structs = Vec::new();
+    for index in indices {
+        let fields = &type_fields[index];
+        let parents = if flat {
+            Vec::new()
+        } else {
+            defs.non(index).parents.clone()
+        };
+
+        let mut inherited = HashSet::new();
+        for &parent in &parents {
+            for (&name, parent_field) in &type_fields[parent] {
+                let composable = fields.get(name).is_some_and(|field| {
+                    field.value_type == parent_field.value_type
+                        && (field.required || !parent_field.required)
+                });
+
+                if !composable || !inherited.insert(name) {
+                    return Err(NonError::Uncomposable {
+                        non: defs.id_of(index).to_string(),
+                        field: name.to_string(),
+                    });
+                }
+            }
+        }
+
+        let mut lines = vec![
+            format!("/// Fields of the `{}` non.", defs.id_of(index)),
+            "#[derive(Debug, Clone, PartialEq, Deserialize)]".to_string(),
+            format!("pub struct {} {{", type_name(defs.id_of(index))),
+        ];
+
+        let own_fields = fields
+            .iter()
+            .filter(|(name, _)| !inherited.contains(*name))
+            .map(|(name, field)| (*name, field_name(name), field))
+            .collect::<Vec<_>>();
+
+        for &parent in &parents {
+            let id = defs.id_of(parent);
+            let mut ident = field_name(id).0;
+            if own_fields.iter().any(|(_, (own, _), _)| *own == ident) {
+                ident.push_str("_non");
+            }
+
+            lines.push("    #[serde(flatten)]".to_string());
+            lines.push(format!("    pub {}: {},", ident, type_name(id)));
+        }
+
+        for (name, (ident, renamed), field) in own_fields {
+            if renamed {
+                lines.push(format!("    #[serde(rename = {:?})]", name));
+            }
+
+            let rust_type = match field.value_type {
+                Some(ValueType::Int) => "i64",
+                Some(ValueType::Float) => "f64",
+                Some(ValueType::Bool) => "bool",
+                Some(ValueType::String) | None => "String",
+            };
+
+            if field.required {
+                lines.push(format!("    pub {}: {},", ident, rust_type));
+            } else {
+                lines.push(format!("    pub {}: Option<{}>,", ident, rust_type));
+            }
+        }
+
+        lines.push("}".to_string());
+        structs.push(lines.join("\n"));
+    }
+
+    Ok(format!(
+        "use serde::Deserialize;\n\n{}",
+        structs.join("\n\n")
+    ))
+}
+
+/// Every field each non and its descendants export, resolved or declared by
+/// the schema, sorted by name, by index.
+pub(crate) fn type_fields(defs: &NonDefs) -> Vec<BTreeMap<&str, TypeField>> {
+    let mut type_fields = defs
+        .field_types()
+        .into_iter()
+        .enumerate()
+        .map(|(index, value_types)| {
+            let values = defs.fields_at(index);
+            let schema = defs.schema_at(index);
+            let hidden = defs.hidden_fields(index);
+
+            values
+                .keys()
+                .chain(schema.keys())
+                .filter(|name| !hidden.contains(name))
+                .map(|&name| {
+                    let field = TypeField {
+                        value_type: value_types.get(&name).copied(),
+                        required: values.contains_key(&name)
+                            || schema.get(&name).is_some_and(|field| field.required),
+                        shared: true,
+                    };
+                    (defs.name(name), field)
+                })
+                .collect::<BTreeMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    for index in 0..defs.nons().len() {
+        let values = defs.fields_at(index);
+        for ancestor in defs.ancestors(index) {
+            let ancestor_values = defs.fields_at(ancestor);
+            for (name, field) in &mut type_fields[ancestor] {
+                let name = defs.interner().get(name);
+                let value = name.and_then(|name| values.get(&name));
+                if value != name.and_then(|name| ancestor_values.get(&name)) {
+                    field.shared = false;
+                }
+            }
+        }
+    }
+
+    type_fields
+}
+
+/// `UpperCamelCase` name of the struct generated for `id`.
+pub(crate) fn type_name(id: &str) -> String {
+    let mut name = String::new();
+    let mut upper = true;
+
+    for c in id.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            name.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+
+    if !name.starts_with(|c: char| c.is_alphabetic()) {
+        name.insert_str(0, "Non");
+    }
+
+    name
+}
+
+/// `snake_case` identifier of the struct field generated for `name`, and
+/// whether it differs from `name` so that the field needs a `serde` rename.
+fn field_name(name: &str) -> (String, bool) {
+    let mut ident = String::new();
+    let mut previous_lower = false;
+
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lower {
+            ident.push('_');
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        ident.extend(c.to_lowercase());
+    }
+
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+
+    if RUST_RESERVED.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+
+    let renamed = ident != name;
+    if RUST_KEYWORDS.contains(&ident.as_str()) {
+        ident.insert_str(0, "r#");
+    }
+
+    (ident, renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{args::OutputFormat, lexer::NonLexer, parser::NonParser};
+
+    /// Checked-in output of [`rust`] for [`SOURCE`], compiled with the tests.
+    mod generated {
+        include!("codegen/fixtures/student.rs");
+    }
+
+    mod generated_flat {
+        include!("codegen/fixtures/student_flat.rs");
+    }
+
+    const SOURCE: &str = "person:
+.name @
+.age 30
+.adult .age >= 18
+.level 1
+!nickname type string
+
+univ:
+.domain 'exemple.tld'
+
+student: person
+.type 'student'
+.mail .name '@' univ.domain
+!quota type float
+
+alice: student
+.age 15 * 2
+.quota 1.5
+
+bob: student
+.age 17
+
+carol: person
+.level 'L1'
+";
+
+    fn defs() -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(SOURCE));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Json)
+            .flat(true)
+            .build()
+            .unwrap()
+    }
+
+    /// The `fields` objects of the flat `json` export, by id.
+    fn exported(defs: &NonDefs) -> BTreeMap<String, serde_json::Value> {
+        let nons: Vec<serde_json::Value> =
+            serde_json::from_str(&defs.serialize().unwrap()).unwrap();
+
+        nons.into_iter()
+            .map(|non| {
+                (
+                    non["id"].as_str().unwrap().to_string(),
+                    non["fields"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generated_structs_are_up_to_date() {
+        let defs = defs();
+        let nons = ["person", "student"].map(|id| defs.index_of(id).unwrap());
+
+        assert_eq!(
+            rust(&defs, &nons, false).unwrap(),
+            include_str!("codegen/fixtures/student.rs").trim_end()
+        );
+        assert_eq!(
+            rust(&defs, &nons, true).unwrap(),
+            include_str!("codegen/fixtures/student_flat.rs").trim_end()
+        );
+    }
+
+    #[test]
+    fn generated_structs_deserialize_the_json_export() {
+        let exported = exported(&defs());
+
+        for id in ["person", "student", "alice", "bob", "carol"] {
+            serde_json::from_value::<generated::Person>(exported[id].clone()).unwrap();
+            serde_json::from_value::<generated_flat::Person>(exported[id].clone()).unwrap();
+        }
+
+        for id in ["student", "alice", "bob"] {
+            serde_json::from_value::<generated::Student>(exported[id].clone()).unwrap();
+            serde_json::from_value::<generated_flat::Student>(exported[id].clone()).unwrap();
+        }
+
+        let alice =
+            serde_json::from_value::<generated::Student>(exported["alice"].clone()).unwrap();
+        assert_eq!(alice.person.name, "alice");
+        assert_eq!(alice.person.age, 30);
+        assert_eq!(alice.quota, Some(1.5));
+        assert_eq!(alice.r#type, "student");
+
+        let bob =
+            serde_json::from_value::<generated_flat::Student>(exported["bob"].clone()).unwrap();
+        assert_eq!((bob.age, bob.adult, bob.quota), (17, false, None));
+
+        // carol disagrees on the type of the level, which all export as text
+        let carol = serde_json::from_value::<generated::Person>(exported["carol"].clone()).unwrap();
+        assert_eq!(carol.level, "L1");
+        assert_eq!(bob.level, "1");
+    }
+}
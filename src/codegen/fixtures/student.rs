@@ -0,0 +1,21 @@
+use serde::Deserialize;
+
+/// Fields of the `person` non.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Person {
+    pub adult: bool,
+    pub age: i64,
+    pub level: String,
+    pub name: String,
+    pub nickname: Option<String>,
+}
+
+/// Fields of the `student` non.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Student {
+    #[serde(flatten)]
+    pub person: Person,
+    pub mail: String,
+    pub quota: Option<f64>,
+    pub r#type: String,
+}
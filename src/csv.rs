@@ -0,0 +1,250 @@
+use crate::{
+    error::{NonError, Result},
+    nds::NonDefs,
+};
+
+/// Which rows and columns the tabular formats write.
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// Columns to write, in order, each being `id` or a field of a written
+    /// non. Defaults to `id` followed by the union of the resolved field names.
+    pub columns: Option<Vec<String>>,
+
+    /// Only write the descendants of this non.
+    pub ancestor: Option<String>,
+}
+
+/// Writes one row per concrete non, with resolved field values and an empty
+/// cell for fields a non does not have.
+pub fn serialize(defs: &NonDefs, options: &CsvOptions, delimiter: char) -> Result<String> {
+    let ancestor = options
+        .ancestor
+        .as_ref()
+        .map(|id| {
+            defs.index_of(id)
+                .ok_or_else(|| NonError::UnknownNon(id.clone()))
+        })
+        .transpose()?;
+
+    let rows = defs
+        .concrete()
+        .into_iter()
+        .filter(|&index| ancestor.is_none_or(|a| defs.ancestors(index).contains(&a)))
+        .collect::<Vec<_>>();
+
+    let mut names = rows
+        .iter()
+        .flat_map(|&index| {
+            let hidden = defs.hidden_fields(index);
+            defs.fields_at(index)
+                .keys()
+                .filter(move |name| !hidden.contains(name))
+        })
+        .map(|&name| defs.name(name).to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names.retain(|name| name != "id");
+    names.insert(0, "id".to_string());
+
+    let columns = match &options.columns {
+        Some(columns) => {
+            if let Some(column) = columns.iter().find(|column| !names.contains(column)) {
+                return Err(NonError::UnknownColumn(column.clone()));
+            }
+            columns.clone()
+        }
+        None => names,
+    };
+
+    let mut str = String::new();
+    str.push_str(&record(&columns, delimiter));
+
+    for index in rows {
+        let values = columns
+            .iter()
+            .map(|column| {
+                if column == "id" {
+                    return Ok(defs.id_of(index).to_string());
+                }
+
+                match defs.interner().get(column) {
+                    Some(field) if defs.fields_at(index).contains_key(&field) => {
+                        defs.get_at(index, field)
+                    }
+                    _ => Ok(String::new()),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        str.push_str(&record(&values, delimiter));
+    }
+
+    Ok(str)
+}
+
+fn record(values: &[String], delimiter: char) -> String {
+    let mut record = values
+        .iter()
+        .map(|value| quote(value, delimiter))
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string());
+    record.push_str("\r\n");
+    record
+}
+
+/// Quotes `value` when it contains the delimiter, a quote or a line break
+/// (RFC 4180).
+fn quote(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reads CSV records (RFC 4180), with quoted cells possibly spanning lines.
+/// Blank lines at the end of `source` are not records.
+pub fn parse(source: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    cell.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if cell.is_empty() => quoted = true,
+            '"' => return Err(NonError::InvalidCsv(line)),
+            c if quoted => {
+                if c == '\n' {
+                    line += 1;
+                }
+                cell.push(c);
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut cell));
+                records.push(std::mem::take(&mut record));
+                line += 1;
+            }
+            c => cell.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(NonError::InvalidCsv(line));
+    }
+
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+
+    while records.last().is_some_and(|record| record == &[""]) {
+        records.pop();
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{args::OutputFormat, lexer::NonLexer, parser::NonParser};
+
+    const SOURCE: &str = "student:
+.login @
+.motto 'Hello, \"world\"'
+
+alice: student
+.age 20
+
+bob: student
+";
+
+    fn defs() -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(SOURCE));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Csv)
+            .flat(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn cells_are_quoted_when_needed() {
+        let output = serialize(&defs(), &CsvOptions::default(), ',').unwrap();
+
+        assert_eq!(
+            output,
+            "id,age,login,motto\r\n\
+             alice,20,alice,\"Hello, \"\"world\"\"\"\r\n\
+             bob,,bob,\"Hello, \"\"world\"\"\"\r\n"
+        );
+        assert_eq!(quote("a;b", ';'), "\"a;b\"");
+        assert_eq!(quote("a,b", ';'), "a,b");
+        assert_eq!(quote("two\nlines", ','), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn columns_must_be_fields() {
+        let options = CsvOptions {
+            columns: Some(vec!["login".to_string(), "id".to_string()]),
+            ancestor: None,
+        };
+        assert_eq!(
+            serialize(&defs(), &options, ',').unwrap(),
+            "login,id\r\nalice,alice\r\nbob,bob\r\n"
+        );
+
+        let options = CsvOptions {
+            columns: Some(vec!["id".to_string(), "mail".to_string()]),
+            ancestor: None,
+        };
+        assert!(matches!(
+            serialize(&defs(), &options, ','),
+            Err(NonError::UnknownColumn(column)) if column == "mail"
+        ));
+    }
+
+    #[test]
+    fn records_are_parsed() {
+        let records = parse(
+            "id,motto\r\na,\"x, \"\"y\"\"\"\r\nb,\"two\nlines\"\n\n",
+            ',',
+        )
+        .unwrap();
+
+        assert_eq!(
+            records,
+            [
+                vec!["id", "motto"],
+                vec!["a", "x, \"y\""],
+                vec!["b", "two\nlines"],
+            ]
+        );
+        assert_eq!(parse("a\tb", '\t').unwrap(), [vec!["a", "b"]]);
+        assert!(matches!(
+            parse("a,b\"c\n", ','),
+            Err(NonError::InvalidCsv(1))
+        ));
+        assert!(matches!(
+            parse("a\n\"b\n", ','),
+            Err(NonError::InvalidCsv(3))
+        ));
+    }
+}
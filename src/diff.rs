@@ -0,0 +1,260 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::Result,
+    nds::NonDefs,
+    non::{NonId, litteral},
+};
+
+/// Differences between two sets of definitions.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<FieldChange>,
+
+    /// Nons whose parents are declared differently, only compared with `raw`.
+    pub parents: Vec<ParentChange>,
+
+    /// Whether values are unresolved `.non` source rather than resolved
+    /// strings.
+    #[serde(skip)]
+    pub raw: bool,
+}
+
+/// A field whose value differs between both sides, `None` when the field is
+/// missing on that side.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub non: String,
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The parents of a non as declared on both sides, with their arguments.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParentChange {
+    pub non: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of added, removed and changed nons and fields.
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len() + self.parents.len()
+    }
+
+    fn value(&self, value: &str) -> String {
+        if self.raw {
+            value.to_string()
+        } else {
+            litteral(value).unwrap_or_else(|_| format!("{:?}", value))
+        }
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for non in &self.added {
+            writeln!(f, "+ {}", non)?;
+        }
+
+        for non in &self.removed {
+            writeln!(f, "- {}", non)?;
+        }
+
+        let parents = |parents: &[String]| match parents {
+            [] => "no parent".to_string(),
+            parents => parents.join(" "),
+        };
+        for change in &self.parents {
+            writeln!(
+                f,
+                "~ {}: {} -> {}",
+                change.non,
+                parents(&change.old),
+                parents(&change.new)
+            )?;
+        }
+
+        for change in &self.changed {
+            write!(f, "~ {}.{}: ", change.non, change.field)?;
+            match (&change.old, &change.new) {
+                (Some(old), Some(new)) => {
+                    writeln!(f, "{} -> {}", self.value(old), self.value(new))?
+                }
+                (None, Some(new)) => writeln!(f, "added {}", self.value(new))?,
+                (Some(old), None) => writeln!(f, "removed {}", self.value(old))?,
+                (None, None) => unreachable!("a changed field exists on one side"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares `old` and `new` non by non.
+///
+/// Nons are matched by id. Resolved values are compared for the nons that are
+/// concrete on both sides, templates being left out since their values only
+/// make sense once inherited. With `raw`, the parents and fields every non
+/// declares itself are compared as written instead.
+pub fn diff(old: &NonDefs, new: &NonDefs, raw: bool) -> Result<Diff> {
+    let mut diff = Diff {
+        raw,
+        ..Diff::default()
+    };
+
+    for non in new.nons() {
+        let id = new.name(non.id());
+        if old.index_of(id).is_none() {
+            diff.added.push(id.to_string());
+        }
+    }
+
+    let old_concrete = old.concrete().into_iter().collect::<HashSet<_>>();
+    let new_concrete = new.concrete().into_iter().collect::<HashSet<_>>();
+
+    for (old_index, non) in old.nons().iter().enumerate() {
+        let id = old.name(non.id());
+        let Some(new_index) = new.index_of(id) else {
+            diff.removed.push(id.to_string());
+            continue;
+        };
+
+        let (old_fields, new_fields) = if raw {
+            let old_parents = parents(old, old_index);
+            let new_parents = parents(new, new_index);
+            if old_parents != new_parents {
+                diff.parents.push(ParentChange {
+                    non: id.to_string(),
+                    old: old_parents,
+                    new: new_parents,
+                });
+            }
+
+            (own_values(old, old_index), own_values(new, new_index))
+        } else if old_concrete.contains(&old_index) && new_concrete.contains(&new_index) {
+            (
+                resolved_values(old, old_index)?,
+                resolved_values(new, new_index)?,
+            )
+        } else {
+            continue;
+        };
+
+        let mut names = old_fields
+            .keys()
+            .chain(new_fields.keys())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let old_value = old_fields.get(name);
+            let new_value = new_fields.get(name);
+
+            if old_value != new_value {
+                diff.changed.push(FieldChange {
+                    non: id.to_string(),
+                    field: name.to_string(),
+                    old: old_value.cloned(),
+                    new: new_value.cloned(),
+                });
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
+fn parents(defs: &NonDefs, index: NonId) -> Vec<String> {
+    defs.non(index)
+        .parents
+        .iter()
+        .map(|&parent| defs.parent_to_string(index, parent))
+        .collect()
+}
+
+fn own_values(defs: &NonDefs, index: NonId) -> HashMap<&str, String> {
+    defs.non(index)
+        .own_fields()
+        .iter()
+        .map(|(&name, value)| (defs.name(name), defs.value_to_string(value)))
+        .collect()
+}
+
+fn resolved_values(defs: &NonDefs, index: NonId) -> Result<HashMap<&str, String>> {
+    defs.fields_at(index)
+        .keys()
+        .map(|&name| Ok((defs.name(name), defs.get_at(index, name)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::NonLexer, parser::NonParser};
+
+    fn defs(source: &str) -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap()
+    }
+
+    const OLD: &str = "univ:\n.domain 'a.tld'\n\nstudent:\n.mail @ '@' univ.domain\n\nalice: student\n\nbob: student\n";
+
+    #[test]
+    fn resolved_values_are_compared() {
+        let new = "univ:\n.domain 'b.tld'\n\nstudent:\n.mail @ '@' univ.domain\n\nalice: student\n.age 20\n\ncarol: student\n";
+        let diff = diff(&defs(OLD), &defs(new), false).unwrap();
+
+        assert_eq!(diff.len(), 5);
+        assert_eq!(
+            diff.to_string(),
+            "+ carol\n\
+             - bob\n\
+             ~ univ.domain: 'a.tld' -> 'b.tld'\n\
+             ~ alice.age: added '20'\n\
+             ~ alice.mail: 'alice@a.tld' -> 'alice@b.tld'\n"
+        );
+    }
+
+    #[test]
+    fn raw_compares_declarations() {
+        let new = "univ:\n.domain 'a.tld'\n\nstudent:\n.mail @ '@' univ.domain\n\nalice: student univ\n\nbob:\n.mail 'bob@a.tld'\n";
+        let diff = diff(&defs(OLD), &defs(new), true).unwrap();
+
+        assert_eq!(
+            diff.to_string(),
+            "~ alice: student -> student univ\n\
+             ~ bob: student -> no parent\n\
+             ~ bob.mail: added 'bob@a.tld'\n"
+        );
+
+        let resolved = super::diff(&defs(OLD), &defs(new), false).unwrap();
+        assert_eq!(resolved.to_string(), "~ alice.domain: added 'a.tld'\n");
+    }
+
+    #[test]
+    fn identical_definitions_have_no_diff() {
+        assert!(diff(&defs(OLD), &defs(OLD), true).unwrap().is_empty());
+        assert!(diff(&defs(OLD), &defs(OLD), false).unwrap().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::token::TokenKind;
+use crate::token::{Span, TokenKind};
 
 pub type Result<T> = std::result::Result<T, NonError>;
 
@@ -15,6 +15,127 @@ pub enum NonError {
     #[error("Token kind {0} cannot have a value.")]
     InvalidTokenKind(TokenKind),
 
+    #[error("Unknown non '{0}'.")]
+    UnknownNon(String),
+
+    #[error("Field '{field}' not found in non '{non}'.")]
+    UnknownField { non: String, field: String },
+
+    #[error("Duplicated field '{0}' without same value.")]
+    DuplicatedField(String),
+
+    #[error("Invalid pattern '{0}'.")]
+    InvalidPattern(String),
+
+    #[error("{0} schema violation(s) found.")]
+    CheckFailed(usize),
+
+    #[error("{0} lint error(s) found.")]
+    LintFailed(usize),
+
+    #[error("{0} difference(s) found.")]
+    DiffFound(usize),
+
+    #[error("Non '{0}' inherits from itself.")]
+    InheritanceCycle(String),
+
+    #[error("Reference cycle: {0}.")]
+    ReferenceCycle(String),
+
+    #[error("Invalid template '{0}'.")]
+    InvalidTemplate(String),
+
+    #[error("Non '{0}' is already defined.")]
+    DuplicatedNon(String),
+
+    #[error("'{0}' is not a valid identifier.")]
+    InvalidIdentifier(String),
+
+    #[error("'{0}' cannot be written as a litteral.")]
+    InvalidLitteral(String),
+
+    #[error("Invalid field value {0:?}.")]
+    InvalidFieldValue(String),
+
+    #[error("Unknown column '{0}'.")]
+    UnknownColumn(String),
+
+    #[error("Column '{0}' is given more than once.")]
+    DuplicatedColumn(String),
+
+    #[error("Field '{0}' cannot be written as an LDAP attribute name.")]
+    InvalidAttribute(String),
+
+    #[error("Invalid CSV at line {0}.")]
+    InvalidCsv(usize),
+
+    #[error("Invalid document: {0}.")]
+    InvalidDocument(String),
+
+    #[error("Field '{0}' must be a string, a number or a boolean.")]
+    UnsupportedValue(String),
+
+    #[error("No field is declared with the same value by every given non.")]
+    NoCommonField,
+
+    #[error("Non '{0}' is given more than once.")]
+    DuplicatedChild(String),
+
+    #[error("A parent can only be extracted from two nons or more.")]
+    TooFewChildren,
+
+    #[error("Environment variable '{0}' is not set.")]
+    MissingEnv(String),
+
+    #[error("Cannot read file '{path}': {reason}.")]
+    UnreadableFile { path: String, reason: String },
+
+    #[error("Reading environment variable '{0}' is denied.")]
+    EnvDenied(String),
+
+    #[error("Reading file '{0}' is denied.")]
+    FileDenied(String),
+
+    #[error("Invalid argument '{value}' for {function}.")]
+    InvalidArgument { function: String, value: String },
+
+    #[error("Cannot apply '{operator}' to '{value}', which is not a number.")]
+    NotANumber { operator: String, value: String },
+
+    #[error("Division by zero.")]
+    DivisionByZero,
+
+    #[error("Overflow in '{0}'.")]
+    Overflow(String),
+
+    #[error("Condition must resolve to 'true' or 'false', found '{0}'.")]
+    NotABoolean(String),
+
+    #[error("{span}: Template '{template}' takes {expected} argument(s), found {found}.")]
+    TemplateArity {
+        span: Span,
+        template: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("{span}: Unknown function '{name}'.")]
+    UnknownFunction { span: Span, name: String },
+
+    #[error("{span}: {function} takes {expected} argument(s), found {found}.")]
+    FunctionArity {
+        span: Span,
+        function: String,
+        expected: String,
+        found: usize,
+    },
+
+    #[error("Parameter '{parameter}' of template '{template}' is not bound.")]
+    UnboundParameter { template: String, parameter: String },
+
+    #[error("Cannot compose '{non}' from its parents at field '{field}', use --flat.")]
+    Uncomposable { non: String, field: String },
+
     #[error("IO error")]
     IoError(#[from] std::io::Error),
 }
@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::error::{NonError, Result};
+
+/// Built-in function callable in a field value, as `name(arg, ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Function {
+    Lower,
+    Upper,
+    Trim,
+    Replace,
+    Substr,
+    Join,
+    Slug,
+    Env,
+    File,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lower" => Some(Self::Lower),
+            "upper" => Some(Self::Upper),
+            "trim" => Some(Self::Trim),
+            "replace" => Some(Self::Replace),
+            "substr" => Some(Self::Substr),
+            "join" => Some(Self::Join),
+            "slug" => Some(Self::Slug),
+            "env" => Some(Self::Env),
+            "file" => Some(Self::File),
+            _ => None,
+        }
+    }
+
+    /// Minimum and maximum number of arguments.
+    pub fn arity(&self) -> (usize, usize) {
+        match self {
+            Self::Lower | Self::Upper | Self::Trim | Self::Slug => (1, 1),
+            Self::Replace => (3, 3),
+            Self::Substr => (2, 3),
+            Self::Join => (2, 2),
+            Self::Env | Self::File => (1, 2),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lower => f.write_str("lower"),
+            Self::Upper => f.write_str("upper"),
+            Self::Trim => f.write_str("trim"),
+            Self::Replace => f.write_str("replace"),
+            Self::Substr => f.write_str("substr"),
+            Self::Join => f.write_str("join"),
+            Self::Slug => f.write_str("slug"),
+            Self::Env => f.write_str("env"),
+            Self::File => f.write_str("file"),
+        }
+    }
+}
+
+/// Lowercases `value` and joins its alphanumeric runs with dashes.
+pub fn slug(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Returns the characters of `value` from `start`, at most `len` of them,
+/// both counted in characters.
+pub fn substr(value: &str, start: &str, len: Option<&str>) -> Result<String> {
+    let start = count(Function::Substr, start)?;
+    let len = len
+        .map(|len| count(Function::Substr, len))
+        .transpose()?
+        .unwrap_or(usize::MAX);
+
+    Ok(value.chars().skip(start).take(len).collect())
+}
+
+fn count(function: Function, value: &str) -> Result<usize> {
+    value.parse().map_err(|_| NonError::InvalidArgument {
+        function: function.to_string(),
+        value: value.to_string(),
+    })
+}
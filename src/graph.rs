@@ -0,0 +1,264 @@
+use std::collections::BTreeSet;
+
+use serde_json::json;
+
+use crate::{
+    args::GraphFormat,
+    error::{NonError, Result},
+    interner::Symbol,
+    nds::NonDefs,
+    non::{Argument, FieldValue, NonId},
+};
+
+/// Which nons the graph shows and how it is written.
+#[derive(Debug, Clone, Default)]
+pub struct GraphOptions {
+    pub format: GraphFormat,
+
+    /// Only show this non and its ancestors.
+    pub ancestors_of: Option<String>,
+
+    /// Only show this non and its descendants.
+    pub descendants_of: Option<String>,
+}
+
+/// Edge of the graph, from a non to another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Edge {
+    /// The non inherits from the other one.
+    Parent(NonId, NonId),
+    /// A field of the non refers to a field of the other one, as in
+    /// `univ.domain`.
+    Reference(NonId, NonId, Symbol),
+    /// The non binds a parameter of a template it inherits from to the other
+    /// one, as in `alice: student(univ)`.
+    Argument(NonId, NonId, Symbol),
+}
+
+/// Draws the nons as a graph, parents above their children, with dashed
+/// edges for the fields referring to other nons and edges labelled with the
+/// parameter for the nons given as template arguments.
+pub fn serialize(defs: &NonDefs, options: &GraphOptions) -> Result<String> {
+    let focus = |id: &Option<String>| {
+        id.as_ref()
+            .map(|id| {
+                defs.index_of(id)
+                    .ok_or_else(|| NonError::UnknownNon(id.clone()))
+            })
+            .transpose()
+    };
+    let ancestors_of = focus(&options.ancestors_of)?;
+    let descendants_of = focus(&options.descendants_of)?;
+
+    let nodes = (0..defs.nons().len())
+        .filter(|&index| {
+            ancestors_of
+                .is_none_or(|focus| index == focus || defs.ancestors(focus).contains(&index))
+                && descendants_of
+                    .is_none_or(|focus| index == focus || defs.ancestors(index).contains(&focus))
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut edges = BTreeSet::new();
+    for &index in &nodes {
+        let non = defs.non(index);
+        edges.extend(
+            non.parents
+                .iter()
+                .map(|&parent| Edge::Parent(index, parent)),
+        );
+
+        for &parent in &non.parents {
+            let params = defs.non(parent).params();
+            let arguments = non.arguments(parent).unwrap_or_default();
+            edges.extend(params.iter().zip(arguments).filter_map(
+                |(&param, argument)| match argument {
+                    Argument::Non(bound) => Some(Edge::Argument(index, *bound, param)),
+                    Argument::Param(_) => None,
+                },
+            ));
+        }
+
+        let mut references = Vec::new();
+        for value in non.own_fields().values() {
+            collect_references(value, &mut references);
+        }
+        edges.extend(
+            references
+                .into_iter()
+                .map(|(other, field)| Edge::Reference(index, other, field)),
+        );
+    }
+    edges.retain(|edge| match *edge {
+        Edge::Parent(from, to) | Edge::Reference(from, to, _) | Edge::Argument(from, to, _) => {
+            nodes.contains(&from) && nodes.contains(&to)
+        }
+    });
+
+    let mut lines = Vec::new();
+    match options.format {
+        GraphFormat::Dot => {
+            lines.push("digraph non {".to_string());
+            lines.push("  rankdir=BT;".to_string());
+            for &index in &nodes {
+                lines.push(format!("  {};", json!(defs.id_of(index))));
+            }
+            for edge in edges {
+                lines.push(match edge {
+                    Edge::Parent(from, to) => format!(
+                        "  {} -> {};",
+                        json!(defs.id_of(from)),
+                        json!(defs.id_of(to))
+                    ),
+                    Edge::Reference(from, to, field) => format!(
+                        "  {} -> {} [style=dashed, label={}];",
+                        json!(defs.id_of(from)),
+                        json!(defs.id_of(to)),
+                        json!(defs.name(field))
+                    ),
+                    Edge::Argument(from, to, param) => format!(
+                        "  {} -> {} [label={}];",
+                        json!(defs.id_of(from)),
+                        json!(defs.id_of(to)),
+                        json!(defs.name(param))
+                    ),
+                });
+            }
+            lines.push("}".to_string());
+        }
+        GraphFormat::Mermaid => {
+            // Mermaid ids are restricted, nons are labelled with their id instead
+            lines.push("flowchart BT".to_string());
+            for &index in &nodes {
+                lines.push(format!("  n{}[{}]", index, json!(defs.id_of(index))));
+            }
+            for edge in edges {
+                lines.push(match edge {
+                    Edge::Parent(from, to) => format!("  n{} --> n{}", from, to),
+                    Edge::Reference(from, to, field) => {
+                        format!("  n{} -.->|{}| n{}", from, json!(defs.name(field)), to)
+                    }
+                    Edge::Argument(from, to, param) => {
+                        format!("  n{} -->|{}| n{}", from, json!(defs.name(param)), to)
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Adds the non and field of every `non.field` reference found in `value`.
+pub(crate) fn collect_references(value: &FieldValue, references: &mut Vec<(NonId, Symbol)>) {
+    match value {
+        FieldValue::ObjRef(non, field) => references.push((*non, *field)),
+        FieldValue::Vec(values)
+        | FieldValue::Interpolated(values)
+        | FieldValue::Call(_, values) => {
+            for value in values {
+                collect_references(value, references);
+            }
+        }
+        FieldValue::Binary(_, left, right) => {
+            collect_references(left, references);
+            collect_references(right, references);
+        }
+        FieldValue::Neg(operand) => collect_references(operand, references),
+        FieldValue::If(condition, then, otherwise) => {
+            collect_references(condition, references);
+            collect_references(then, references);
+            collect_references(otherwise, references);
+        }
+        FieldValue::Litteral(_)
+        | FieldValue::FieldReference(_)
+        | FieldValue::Number(_)
+        | FieldValue::Bool(_)
+        | FieldValue::ParamRef(..)
+        | FieldValue::Is(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::NonLexer, parser::NonParser};
+
+    const SOURCE: &str = "univ:
+.domain 'exemple.tld'
+
+student(school):
+.mail @ '@' school.domain
+.site univ.domain
+
+alice: student(univ)
+";
+
+    fn graph(options: &GraphOptions) -> String {
+        let mut parser = NonParser::new(NonLexer::new(SOURCE));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap();
+
+        serialize(&defs, options).unwrap()
+    }
+
+    #[test]
+    fn dot_shows_every_edge() {
+        assert_eq!(
+            graph(&GraphOptions::default()),
+            r#"digraph non {
+  rankdir=BT;
+  "univ";
+  "student";
+  "alice";
+  "alice" -> "student";
+  "student" -> "univ" [style=dashed, label="domain"];
+  "alice" -> "univ" [label="school"];
+}"#
+        );
+    }
+
+    #[test]
+    fn mermaid_shows_every_edge() {
+        let options = GraphOptions {
+            format: GraphFormat::Mermaid,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            graph(&options),
+            r#"flowchart BT
+  n0["univ"]
+  n1["student"]
+  n2["alice"]
+  n2 --> n1
+  n1 -.->|"domain"| n0
+  n2 -->|"school"| n0"#
+        );
+    }
+
+    #[test]
+    fn focus_keeps_the_lineage() {
+        let options = GraphOptions {
+            ancestors_of: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            graph(&options),
+            "digraph non {\n  rankdir=BT;\n  \"student\";\n  \"alice\";\n  \"alice\" -> \"student\";\n}"
+        );
+
+        let options = GraphOptions {
+            descendants_of: Some("univ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            graph(&options),
+            "digraph non {\n  rankdir=BT;\n  \"univ\";\n}"
+        );
+    }
+}
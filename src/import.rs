@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    csv,
+    error::{NonError, Result},
+    interner::Interner,
+    nds::NonDefs,
+    non::{FieldValue, Non, is_identifier, is_litteral, litteral},
+};
+
+/// A non read from an external record.
+#[derive(Debug, Clone)]
+pub struct ImportedNon {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub fields: Vec<(String, ImportedValue)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportedValue {
+    /// A plain value, written as a litteral.
+    Litteral(String),
+
+    /// A value already written as `.non` source, as in non-flat exports.
+    Source(String),
+
+    /// A number, negative ones starting with `-`.
+    Number(String),
+
+    /// A boolean, written as `true` or `false`.
+    Bool(bool),
+}
+
+impl ImportedNon {
+    /// Writes the non as `.non` source.
+    pub fn serialize_non(&self) -> Result<String> {
+        let mut interner = Interner::new();
+        let non = Non::from_id(interner.intern(&self.id));
+        let parents = self.parents.iter().map(String::as_str).collect::<Vec<_>>();
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    ImportedValue::Litteral(value) => litteral(value)?,
+                    ImportedValue::Source(source) | ImportedValue::Number(source) => source.clone(),
+                    ImportedValue::Bool(value) => value.to_string(),
+                };
+                Ok((name.as_str(), value))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(non.serialize_non(&interner, &parents, &fields, false))
+    }
+
+    /// Adds the non to `defs`, its parents must already be defined there.
+    /// Source values are not parsed, so only litteral ones are accepted.
+    pub fn add_to(&self, defs: &mut NonDefs) -> Result<()> {
+        let parents = self.parents.iter().map(String::as_str).collect::<Vec<_>>();
+        let fields = self
+            .fields
+            .iter()
+            .map(|(name, value)| match value {
+                ImportedValue::Litteral(value) => {
+                    Ok((name.as_str(), FieldValue::Litteral(value.clone())))
+                }
+                ImportedValue::Number(number) => Ok((name.as_str(), number_value(number))),
+                ImportedValue::Bool(value) => Ok((name.as_str(), FieldValue::Bool(*value))),
+                ImportedValue::Source(source) => Err(NonError::InvalidLitteral(source.clone())),
+            })
+            .collect::<Result<_>>()?;
+
+        defs.add_non(&self.id, &parents, fields).map(|_| ())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !is_identifier(&self.id) {
+            return Err(NonError::InvalidIdentifier(self.id.clone()));
+        }
+
+        for (name, value) in &self.fields {
+            if !is_identifier(name) {
+                return Err(NonError::InvalidIdentifier(name.clone()));
+            }
+
+            if let ImportedValue::Litteral(value) = value
+                && !is_litteral(value)
+            {
+                return Err(NonError::InvalidLitteral(value.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns every row of a CSV file with a header into a non inheriting from
+/// `parent`, identified by the `id_column` cell. Empty cells are left out so
+/// the inherited value applies. Columns and ids must be unique, and no id can
+/// be `parent` itself.
+pub fn from_csv(
+    source: &str,
+    delimiter: char,
+    id_column: &str,
+    parent: &str,
+) -> Result<Vec<ImportedNon>> {
+    let mut records = csv::parse(source, delimiter)?.into_iter();
+    let header = records.next().unwrap_or_default();
+    if let Some((_, name)) = header
+        .iter()
+        .enumerate()
+        .find(|(column, name)| header[..*column].contains(name))
+    {
+        return Err(NonError::DuplicatedColumn(name.to_string()));
+    }
+
+    let id_index = header
+        .iter()
+        .position(|column| column == id_column)
+        .ok_or_else(|| NonError::UnknownColumn(id_column.to_string()))?;
+
+    let mut ids = HashSet::new();
+    records
+        .enumerate()
+        .map(|(row, record)| {
+            if record.len() != header.len() {
+                return Err(NonError::InvalidCsv(row + 2));
+            }
+
+            let id = &record[id_index];
+            if id == parent {
+                return Err(NonError::InheritanceCycle(id.clone()));
+            }
+            if !ids.insert(id.clone()) {
+                return Err(NonError::DuplicatedNon(id.clone()));
+            }
+
+            let fields = header
+                .iter()
+                .zip(&record)
+                .enumerate()
+                .filter(|(column, (_, value))| *column != id_index && !value.is_empty())
+                .map(|(_, (name, value))| (name.clone(), ImportedValue::Litteral(value.clone())))
+                .collect();
+
+            let non = ImportedNon {
+                id: record[id_index].clone(),
+                parents: vec![parent.to_string()],
+                fields,
+            };
+            non.validate()?;
+            Ok(non)
+        })
+        .collect()
+}
+
+/// Reads nons from a JSON document, see [`from_value`].
+pub fn from_json(source: &str, raw: bool) -> Result<Vec<ImportedNon>> {
+    let document =
+        serde_json::from_str(source).map_err(|err| NonError::InvalidDocument(err.to_string()))?;
+    from_value(document, raw)
+}
+
+/// Reads nons from a YAML document, see [`from_value`].
+pub fn from_yaml(source: &str, raw: bool) -> Result<Vec<ImportedNon>> {
+    let document =
+        serde_yaml::from_str(source).map_err(|err| NonError::InvalidDocument(err.to_string()))?;
+    from_value(document, raw)
+}
+
+/// Reads nons from either an array of objects carrying their `id`, or a map
+/// from ids to objects, as written by the `json` and `yaml` formats.
+///
+/// An object either holds its fields in a `fields` object, or is a plain
+/// object whose other keys are fields. An array under `parents` lists the
+/// parents. Numbers and booleans keep their type, other scalars become
+/// litterals, unless `raw` is set, in which case strings are read as `.non`
+/// source like the non-flat exports write them.
+pub fn from_value(document: Value, raw: bool) -> Result<Vec<ImportedNon>> {
+    let entries = match document {
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::Object(mut object) => match object.remove("id") {
+                    Some(Value::String(id)) => Ok((id, object)),
+                    _ => Err(NonError::InvalidDocument(
+                        "every object needs a string 'id'".to_string(),
+                    )),
+                },
+                _ => Err(NonError::InvalidDocument(
+                    "expected an array of objects".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(id, value)| match value {
+                Value::Object(object) => Ok((id, object)),
+                Value::Null => Ok((id, Map::new())),
+                _ => Err(NonError::InvalidDocument(format!(
+                    "expected an object for '{}'",
+                    id
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        _ => {
+            return Err(NonError::InvalidDocument(
+                "expected an array or a map of objects".to_string(),
+            ));
+        }
+    };
+
+    entries
+        .into_iter()
+        .map(|(id, object)| {
+            let non = imported_non(id, object, raw)?;
+            non.validate()?;
+            Ok(non)
+        })
+        .collect()
+}
+
+/// Writes `number` the way `.non` numbers are written, without an exponent.
+fn number_source(number: &serde_json::Number) -> String {
+    let source = number.to_string();
+    match number.as_f64() {
+        Some(value) if source.contains(['e', 'E']) => value.to_string(),
+        _ => source,
+    }
+}
+
+fn number_value(number: &str) -> FieldValue {
+    match number.strip_prefix('-') {
+        Some(number) => FieldValue::Neg(Box::new(FieldValue::Number(number.to_string()))),
+        None => FieldValue::Number(number.to_string()),
+    }
+}
+
+fn imported_non(id: String, mut object: Map<String, Value>, raw: bool) -> Result<ImportedNon> {
+    let parents = match object.remove("parents") {
+        Some(Value::Array(parents)) => parents
+            .into_iter()
+            .map(|parent| match parent {
+                Value::String(parent) => Ok(parent),
+                _ => Err(NonError::InvalidDocument(format!(
+                    "parents of '{}' must be strings",
+                    id
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        Some(value) => {
+            object.insert("parents".to_string(), value);
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+
+    let fields = match object.remove("fields") {
+        Some(Value::Object(fields)) if object.is_empty() => fields,
+        Some(value) => {
+            object.insert("fields".to_string(), value);
+            object
+        }
+        None => object,
+    };
+
+    let fields = fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(value) if raw => ImportedValue::Source(value),
+                Value::String(value) => ImportedValue::Litteral(value),
+                Value::Bool(value) => ImportedValue::Bool(value),
+                Value::Number(value) => ImportedValue::Number(number_source(&value)),
+                _ => return Err(NonError::UnsupportedValue(name)),
+            };
+            Ok((name, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ImportedNon {
+        id,
+        parents,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{args::OutputFormat, lexer::NonLexer, parser::NonParser};
+
+    fn export(source: &str) -> Value {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+
+        let output = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Json)
+            .flat(true)
+            .build()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        serde_json::from_str(&output).unwrap()
+    }
+
+    #[test]
+    fn scalars_keep_their_type() {
+        let document = r#"[
+            {"id": "alice", "fields": {
+                "login": "alice", "age": 30, "balance": -2.5, "admin": true, "guest": false
+            }}
+        ]"#;
+        let source = from_json(document, false)
+            .unwrap()
+            .iter()
+            .map(|non| non.serialize_non().unwrap())
+            .collect::<String>();
+
+        let exported = export(&source);
+        let original = serde_json::from_str::<Value>(document).unwrap();
+        assert_eq!(exported, original);
+        assert_eq!(exported[0]["fields"]["admin"], Value::Bool(true));
+        assert_eq!(exported[0]["fields"]["balance"], serde_json::json!(-2.5));
+    }
+
+    #[test]
+    fn csv_rows_become_nons() {
+        let nons = from_csv(
+            "id,name\r\nalice,Alice\r\nbob,\r\n\r\n",
+            ',',
+            "id",
+            "student",
+        )
+        .unwrap();
+
+        assert_eq!(nons.len(), 2);
+        assert_eq!(nons[0].parents, ["student"]);
+        assert!(
+            matches!(&nons[0].fields[..], [(name, ImportedValue::Litteral(value))] if name == "name" && value == "Alice")
+        );
+        assert!(nons[1].fields.is_empty());
+    }
+
+    #[test]
+    fn csv_ids_and_columns_are_unique() {
+        assert!(matches!(
+            from_csv("id,name\nalice,A\nalice,B\n", ',', "id", "student"),
+            Err(NonError::DuplicatedNon(id)) if id == "alice"
+        ));
+        assert!(matches!(
+            from_csv("id,name\nstudent,A\n", ',', "id", "student"),
+            Err(NonError::InheritanceCycle(id)) if id == "student"
+        ));
+        assert!(matches!(
+            from_csv("id,name,name\nalice,A,B\n", ',', "id", "student"),
+            Err(NonError::DuplicatedColumn(name)) if name == "name"
+        ));
+        assert!(matches!(
+            from_csv("id,name\nalice\n", ',', "id", "student"),
+            Err(NonError::InvalidCsv(2))
+        ));
+    }
+
+    #[test]
+    fn exponents_are_written_out() {
+        let nons = from_json(r#"{"a": {"big": 1e21, "small": 2.5e-7}}"#, false).unwrap();
+
+        let fields = &nons[0].fields;
+        assert!(
+            matches!(&fields[0].1, ImportedValue::Number(number) if number == "1000000000000000000000")
+        );
+        assert!(matches!(&fields[1].1, ImportedValue::Number(number) if number == "0.00000025"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Serialize;
+
+/// Handle to a string stored in an [`Interner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The implicit `id` field, referenced in sources with `@`.
+    pub const ID: Symbol = Symbol(0);
+}
+
+/// Deduplicates identifiers and field names so each distinct name is
+/// allocated once and compared as an integer afterwards.
+#[derive(Debug, Clone)]
+pub struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        let mut interner = Self {
+            symbols: HashMap::new(),
+            strings: Vec::new(),
+        };
+        interner.intern("id");
+        interner
+    }
+
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(s) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.strings.len() as u32);
+        let string = Arc::<str>::from(s);
+        self.strings.push(string.clone());
+        self.symbols.insert(string, symbol);
+        symbol
+    }
+
+    /// Looks `s` up without interning it.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.symbols.get(s).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::Result,
+    interner::Symbol,
+    nds::NonDefs,
+    non::NonId,
+    schema::{FieldSchema, ValueType, json_value},
+};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Builds a JSON Schema validating the output of the `json` format.
+///
+/// Every non gets a definition under `$defs` describing the objects it and
+/// its descendants export, and the document itself accepts an array of any of
+/// them. Inherited fields are always described; when `flat` is false they are
+/// only required if the non declares them itself, as in the non-flat export,
+/// and schema constraints are left out since values are not resolved.
+pub fn serialize(defs: &NonDefs, flat: bool) -> Result<String> {
+    let mut definitions = Map::new();
+    let mut refs = Vec::new();
+    let field_types = defs.field_types();
+
+    for (index, value_types) in field_types.iter().enumerate() {
+        let id = defs.id_of(index);
+        definitions.insert(id.to_string(), non_schema(defs, index, value_types, flat));
+        refs.push(json!({ "$ref": format!("#/$defs/{}", id) }));
+    }
+
+    let document = json!({
+        "$schema": DIALECT,
+        "type": "array",
+        "items": { "anyOf": refs },
+        "$defs": definitions,
+    });
+
+    Ok(serde_json::to_string_pretty(&document).unwrap())
+}
+
+fn non_schema(
+    defs: &NonDefs,
+    index: NonId,
+    value_types: &HashMap<Symbol, ValueType>,
+    flat: bool,
+) -> Value {
+    let non = defs.non(index);
+    let schema = defs.schema_at(index);
+
+    let mut field_names = defs
+        .fields_at(index)
+        .keys()
+        .chain(schema.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    field_names.sort_by_key(|&name| defs.name(name));
+    field_names.dedup();
+    field_names.retain(|name| {
+        !schema
+            .get(name)
+            .is_some_and(|field_schema| field_schema.hidden)
+    });
+
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for name in field_names {
+        let field_schema = schema.get(&name).cloned().unwrap_or_default();
+        let declared = if flat {
+            defs.fields_at(index).contains_key(&name)
+        } else {
+            non.own_fields().contains_key(&name)
+        };
+
+        if declared || (flat && field_schema.required) {
+            required.push(defs.name(name).to_string());
+        }
+
+        properties.insert(
+            defs.name(name).to_string(),
+            field_property(&field_schema, value_types.get(&name).copied(), flat),
+        );
+    }
+
+    let mut object_properties = Map::new();
+    object_properties.insert("id".to_string(), json!({ "type": "string" }));
+
+    if !flat {
+        object_properties.insert(
+            "parents".to_string(),
+            json!({ "type": "array", "items": { "type": "string" } }),
+        );
+    }
+
+    let mut object_required = vec!["id"];
+    if !required.is_empty() {
+        object_required.push("fields");
+    }
+
+    object_properties.insert(
+        "fields".to_string(),
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        }),
+    );
+
+    json!({
+        "type": "object",
+        "properties": object_properties,
+        "required": object_required,
+    })
+}
+
+/// Maps the constraints and type of a field to a JSON Schema property. The
+/// non-flat export writes unresolved source values, so they only apply when
+/// `flat`.
+fn field_property(field_schema: &FieldSchema, value_type: Option<ValueType>, flat: bool) -> Value {
+    if !flat {
+        return json!({ "type": "string" });
+    }
+
+    let mut property = Map::new();
+    property.insert(
+        "type".to_string(),
+        json!(value_type.unwrap_or(ValueType::String).json_type()),
+    );
+
+    if let Some(pattern) = &field_schema.pattern
+        && matches!(value_type, None | Some(ValueType::String))
+    {
+        property.insert("pattern".to_string(), json!(format!("^(?:{})$", pattern)));
+    }
+
+    if !field_schema.values.is_empty() {
+        let values = field_schema
+            .values
+            .iter()
+            .map(|value| json_value(value_type, value))
+            .collect::<Vec<_>>();
+        property.insert("enum".to_string(), Value::Array(values));
+    }
+
+    Value::Object(property)
+}
@@ -0,0 +1,262 @@
+use crate::{
+    error::{NonError, Result},
+    nds::NonDefs,
+    non::NonId,
+};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How concrete nons are mapped to LDAP entries.
+#[derive(Debug, Clone)]
+pub struct LdifOptions {
+    /// DN of each entry, where `{field}` is replaced by the resolved value of
+    /// `field` and `{id}` by the id of the non.
+    pub dn: String,
+
+    /// Object classes given to the descendants of a non, as
+    /// `(non id, classes)` pairs.
+    pub object_classes: Vec<(String, Vec<String>)>,
+}
+
+impl Default for LdifOptions {
+    fn default() -> Self {
+        Self {
+            dn: "cn={id}".to_string(),
+            object_classes: Vec::new(),
+        }
+    }
+}
+
+/// Writes one LDIF entry per concrete non, with every resolved field as an
+/// attribute, see [`attribute_name`].
+pub fn serialize(defs: &NonDefs, options: &LdifOptions) -> Result<String> {
+    let mut str = String::from("version: 1\n");
+
+    for index in defs.concrete() {
+        str.push('\n');
+        str.push_str(&attribute("dn", &dn(defs, index, &options.dn)?));
+
+        for class in object_classes(defs, index, options) {
+            str.push_str(&attribute("objectClass", &class));
+        }
+
+        let hidden = defs.hidden_fields(index);
+        let mut fields = defs
+            .fields_at(index)
+            .keys()
+            .copied()
+            .filter(|field| !hidden.contains(field))
+            .collect::<Vec<_>>();
+        fields.sort_by_key(|&field| defs.name(field));
+
+        for field in fields {
+            let name = attribute_name(defs.name(field))?;
+            str.push_str(&attribute(&name, &defs.get_at(index, field)?));
+        }
+    }
+
+    Ok(str)
+}
+
+fn dn(defs: &NonDefs, index: NonId, template: &str) -> Result<String> {
+    let mut dn = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| NonError::InvalidTemplate(template.to_string()))?;
+        let field = &rest[start + 1..end];
+
+        let value = if field == "id" {
+            defs.id_of(index).to_string()
+        } else {
+            defs.get(defs.id_of(index), field)?
+        };
+
+        dn.push_str(&rest[..start]);
+        dn.push_str(&escape_dn_value(&value));
+        rest = &rest[end + 1..];
+    }
+
+    dn.push_str(rest);
+    Ok(dn)
+}
+
+/// `top`, followed by the classes mapped to the non and its ancestors.
+fn object_classes(defs: &NonDefs, index: NonId, options: &LdifOptions) -> Vec<String> {
+    let mut lineage = defs.ancestors(index);
+    lineage.push(index);
+
+    let mut classes = vec!["top".to_string()];
+
+    for non in lineage {
+        let id = defs.id_of(non);
+        for (_, mapped) in options.object_classes.iter().filter(|(n, _)| n == id) {
+            for class in mapped {
+                if !classes.contains(class) {
+                    classes.push(class.clone());
+                }
+            }
+        }
+    }
+
+    classes
+}
+
+/// Escapes the special characters of an attribute value used in a DN
+/// (RFC 4514).
+fn escape_dn_value(value: &str) -> String {
+    let mut escaped = String::new();
+    let last = value.chars().count().saturating_sub(1);
+
+    for (i, c) in value.chars().enumerate() {
+        let leading = i == 0 && (c == ' ' || c == '#');
+        let trailing = i == last && c == ' ';
+
+        if leading || trailing || matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Maps the field `name` to an attribute name, which starts with a letter
+/// followed by letters, digits and hyphens (RFC 4512), `_` being written as
+/// `-`.
+fn attribute_name(name: &str) -> Result<String> {
+    let attribute = name.replace('_', "-");
+    let valid = attribute.starts_with(|c: char| c.is_ascii_alphabetic())
+        && attribute
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if valid {
+        Ok(attribute)
+    } else {
+        Err(NonError::InvalidAttribute(name.to_string()))
+    }
+}
+
+/// Writes `name: value`, switching to `name:: base64` when the value is not
+/// a SAFE-STRING (RFC 2849).
+fn attribute(name: &str, value: &str) -> String {
+    if is_safe_string(value) {
+        format!("{}: {}\n", name, value)
+    } else {
+        format!("{}:: {}\n", name, base64(value.as_bytes()))
+    }
+}
+
+fn is_safe_string(value: &str) -> bool {
+    let first_is_safe = !value.starts_with([' ', ':', '<']);
+    let chars_are_safe = value
+        .chars()
+        .all(|c| c.is_ascii() && !matches!(c, '\0' | '\n' | '\r'));
+
+    first_is_safe && chars_are_safe && !value.ends_with(' ')
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let buf = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (buf[0] as u32) << 16 | (buf[1] as u32) << 8 | buf[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{args::OutputFormat, lexer::NonLexer, parser::NonParser};
+
+    fn defs(source: &str) -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Ldif)
+            .flat(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn entries_are_written() {
+        let source = "student:\n.base_quota 10\n.cn 'Doe, Jane'\n\njane: student\n";
+        let options = LdifOptions {
+            dn: "cn={cn},ou=people".to_string(),
+            object_classes: vec![("student".to_string(), vec!["person".to_string()])],
+        };
+
+        assert_eq!(
+            serialize(&defs(source), &options).unwrap(),
+            "version: 1\n\n\
+             dn: cn=Doe\\, Jane,ou=people\n\
+             objectClass: top\n\
+             objectClass: person\n\
+             base-quota: 10\n\
+             cn: Doe, Jane\n"
+        );
+    }
+
+    #[test]
+    fn attribute_names_are_checked() {
+        assert_eq!(attribute_name("base_quota").unwrap(), "base-quota");
+        assert!(matches!(
+            attribute_name("2fa"),
+            Err(NonError::InvalidAttribute(name)) if name == "2fa"
+        ));
+        assert!(matches!(
+            attribute_name("_login"),
+            Err(NonError::InvalidAttribute(name)) if name == "_login"
+        ));
+        assert!(attribute_name("quota_é").is_err());
+    }
+
+    #[test]
+    fn unsafe_values_are_base64() {
+        assert_eq!(attribute("cn", "Jane"), "cn: Jane\n");
+        assert_eq!(attribute("cn", " Jane"), "cn:: IEphbmU=\n");
+        assert_eq!(attribute("cn", "Jane "), "cn:: SmFuZSA=\n");
+        assert_eq!(attribute("cn", ":Jane"), "cn:: OkphbmU=\n");
+        assert_eq!(attribute("cn", "<Jane"), "cn:: PEphbmU=\n");
+        assert_eq!(attribute("cn", "Zoé"), "cn:: Wm/DqQ==\n");
+        assert_eq!(attribute("cn", "a\nb"), "cn:: YQpi\n");
+        assert_eq!(attribute("cn", "a:b<c "), "cn:: YTpiPGMg\n");
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+    }
+
+    #[test]
+    fn dn_values_are_escaped() {
+        assert_eq!(escape_dn_value("Doe, Jane"), "Doe\\, Jane");
+        assert_eq!(escape_dn_value(" #1 "), "\\ #1\\ ");
+        assert_eq!(
+            escape_dn_value("#a+b;c<d>\"e\\"),
+            "\\#a\\+b\\;c\\<d\\>\\\"e\\\\"
+        );
+    }
+}
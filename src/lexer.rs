@@ -1,56 +1,107 @@
 use nom::{
     IResult, Parser,
     branch::alt,
-    bytes::complete::take_while1,
+    bytes::complete::{tag, take_while, take_while1},
     character::{
         char,
-        complete::{newline, space1},
+        complete::{digit1, newline, satisfy, space1},
     },
+    combinator::{not, opt, recognize},
+    sequence::terminated,
 };
 
 use crate::{
     error::{NonError, Result},
-    token::{Token, TokenKind},
+    token::{Span, Token, TokenKind},
 };
 
-fn parse_char_to_token(s: &str, c: char, token: Token) -> IResult<&str, Token> {
+fn parse_char_to_token<'a>(s: &'a str, c: char, token: Token<'a>) -> IResult<&'a str, Token<'a>> {
     char(c).parse(s).map(|(rest, _)| (rest, token))
 }
 
-fn parse_identifier(s: &str) -> IResult<&str, Token> {
+fn parse_identifier(s: &str) -> IResult<&str, Token<'_>> {
     take_while1(|c: char| c.is_alphanumeric() || c == '_')
         .parse(s)
-        .map(|(rest, id)| {
-            (
-                rest,
-                Token::new(TokenKind::Identifier, Some(id.to_string())),
-            )
-        })
+        .map(|(rest, id)| (rest, Token::new(TokenKind::Identifier, Some(id))))
+}
+
+/// Digits with an optional fractional part, not directly followed by
+/// identifier characters so that ids such as `2fa` stay identifiers.
+fn parse_number(s: &str) -> IResult<&str, Token<'_>> {
+    terminated(
+        recognize((digit1, opt((char('.'), digit1)))),
+        not(satisfy(|c: char| c.is_alphanumeric() || c == '_')),
+    )
+    .parse(s)
+    .map(|(rest, number)| (rest, Token::new(TokenKind::Number, Some(number))))
+}
+
+fn parse_operator(s: &str) -> IResult<&str, Token<'_>> {
+    alt((
+        tag("=="),
+        tag("!="),
+        tag("<="),
+        tag(">="),
+        tag("<"),
+        tag(">"),
+        tag("+"),
+        tag("*"),
+        tag("/"),
+        tag("%"),
+    ))
+    .parse(s)
+    .map(|(rest, operator)| (rest, Token::new(TokenKind::Operator, Some(operator))))
 }
 
-fn parse_string_litteral(s: &str) -> IResult<&str, Token> {
+fn parse_string_litteral(s: &str) -> IResult<&str, Token<'_>> {
     (char('\''), take_while1(|c| c != '\''), char('\''))
         .parse(s)
-        .map(|(rest, (_, s, _))| (rest, Token::new(TokenKind::Litteral, Some(s.to_string()))))
+        .map(|(rest, (_, s, _))| (rest, Token::new(TokenKind::Litteral, Some(s))))
+}
+
+fn parse_interpolated(s: &str) -> IResult<&str, Token<'_>> {
+    (char('"'), take_while(|c| c != '"' && c != '\n'), char('"'))
+        .parse(s)
+        .map(|(rest, (_, s, _))| (rest, Token::new(TokenKind::Interpolated, Some(s))))
 }
 
-fn parse_dot(s: &str) -> IResult<&str, Token> {
+fn parse_dot(s: &str) -> IResult<&str, Token<'_>> {
     parse_char_to_token(s, '.', Token::from(TokenKind::Dot))
 }
 
-fn parse_at(s: &str) -> IResult<&str, Token> {
+fn parse_at(s: &str) -> IResult<&str, Token<'_>> {
     parse_char_to_token(s, '@', Token::from(TokenKind::At))
 }
 
-fn parse_colon(s: &str) -> IResult<&str, Token> {
+fn parse_bang(s: &str) -> IResult<&str, Token<'_>> {
+    parse_char_to_token(s, '!', Token::from(TokenKind::Bang))
+}
+
+fn parse_minus(s: &str) -> IResult<&str, Token<'_>> {
+    parse_char_to_token(s, '-', Token::from(TokenKind::Minus))
+}
+
+fn parse_lparen(s: &str) -> IResult<&str, Token<'_>> {
+    parse_char_to_token(s, '(', Token::from(TokenKind::LParen))
+}
+
+fn parse_rparen(s: &str) -> IResult<&str, Token<'_>> {
+    parse_char_to_token(s, ')', Token::from(TokenKind::RParen))
+}
+
+fn parse_comma(s: &str) -> IResult<&str, Token<'_>> {
+    parse_char_to_token(s, ',', Token::from(TokenKind::Comma))
+}
+
+fn parse_colon(s: &str) -> IResult<&str, Token<'_>> {
     parse_char_to_token(s, ':', Token::from(TokenKind::Colon))
 }
 
-fn parse_whitespace(s: &str) -> IResult<&str, Token> {
+fn parse_whitespace(s: &str) -> IResult<&str, Token<'_>> {
     space1(s).map(|(rest, _)| (rest, Token::from(TokenKind::Space)))
 }
 
-fn parse_newline(s: &str) -> IResult<&str, Token> {
+fn parse_newline(s: &str) -> IResult<&str, Token<'_>> {
     newline
         .parse(s)
         .map(|(rest, _)| (rest, Token::from(TokenKind::Newline)))
@@ -59,38 +110,106 @@ fn parse_newline(s: &str) -> IResult<&str, Token> {
 #[derive(Debug)]
 pub struct NonLexer<'a> {
     remaining: &'a str,
+    position: Span,
 }
 
 impl<'a> NonLexer<'a> {
     pub fn new(source: &'a str) -> Self {
-        Self { remaining: source }
+        Self::with_position(source, Span::default())
+    }
+
+    /// Lexes `source` as if it started at `position` of an enclosing source.
+    pub fn with_position(source: &'a str, position: Span) -> Self {
+        Self {
+            remaining: source,
+            position,
+        }
     }
 
-    pub fn read_next_token(&mut self) -> Result<Token> {
+    pub fn read_next_token(&mut self) -> Result<Token<'a>> {
+        self.skip_comments();
+
         alt((
+            parse_number,
             parse_identifier,
             parse_string_litteral,
+            parse_interpolated,
             parse_whitespace,
             parse_dot,
             parse_colon,
             parse_at,
+            parse_operator,
+            parse_bang,
+            parse_minus,
+            parse_lparen,
+            parse_rparen,
+            parse_comma,
             parse_newline,
         ))
         .parse(self.remaining)
-        .map(|(remaining, token)| {
+        .map(|(remaining, mut token)| {
+            token.span = self.position;
+            self.advance_position(&self.remaining[..self.remaining.len() - remaining.len()]);
             self.remaining = remaining;
             token
         })
         .map_err(|_| NonError::TokenizeFailed)
     }
 
-    pub fn _read_all(&mut self) -> Vec<Token> {
+    /// Skips a `# ...` comment up to the end of its line. A comment alone on
+    /// its line is skipped along with the line, which then does not count as
+    /// a blank line.
+    fn skip_comments(&mut self) {
+        loop {
+            let line_start = self.position.column == 1;
+            let source = if line_start {
+                self.remaining.trim_start_matches([' ', '\t'])
+            } else {
+                self.remaining
+            };
+
+            if !source.starts_with('#') {
+                return;
+            }
+
+            let mut end = source.find('\n').unwrap_or(source.len());
+            if line_start && end < source.len() {
+                end += 1;
+            }
+
+            let consumed = self.remaining.len() - source.len() + end;
+            self.advance_position(&self.remaining[..consumed]);
+            self.remaining = &self.remaining[consumed..];
+
+            if !line_start {
+                return;
+            }
+        }
+    }
+
+    /// Returns whether all of the source was read.
+    pub fn is_empty(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    fn advance_position(&mut self, consumed: &str) {
+        for c in consumed.chars() {
+            if c == '\n' {
+                self.position.line += 1;
+                self.position.column = 1;
+            } else {
+                self.position.column += 1;
+            }
+        }
+    }
+
+    pub fn _read_all(&mut self) -> Vec<Token<'a>> {
         self.into_iter().collect()
     }
 }
 
 impl<'a> Iterator for NonLexer<'a> {
-    type Item = Token;
+    type Item = Token<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.read_next_token().ok()
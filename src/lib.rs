@@ -0,0 +1,20 @@
+pub mod args;
+pub mod codegen;
+pub mod csv;
+pub mod diff;
+pub mod error;
+pub mod function;
+pub mod graph;
+pub mod import;
+pub mod interner;
+pub mod json_schema;
+pub mod ldif;
+pub mod lexer;
+pub mod lint;
+pub mod nds;
+pub mod non;
+pub mod operator;
+pub mod parser;
+pub mod schema;
+pub mod token;
+pub mod typescript;
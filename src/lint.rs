@@ -0,0 +1,507 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{NonError, Result},
+    graph::collect_references,
+    nds::NonDefs,
+    non::{Argument, FieldValue, NonId},
+    token::Span,
+};
+
+/// Check run by the linter, named in configuration files and suppression
+/// comments in kebab case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// A template nothing inherits from nor refers to, templates being the
+    /// nons with parameters, a schema or no parent of their own.
+    UnusedTemplate,
+    /// A field declared with the value it already inherits.
+    IdenticalOverride,
+    /// A parent already inherited through another parent.
+    RedundantParent,
+    /// A `non.field` reference to a non others inherit from.
+    AbstractReference,
+    /// An id breaking the naming convention.
+    Naming,
+}
+
+impl Rule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unused-template" => Some(Self::UnusedTemplate),
+            "identical-override" => Some(Self::IdenticalOverride),
+            "redundant-parent" => Some(Self::RedundantParent),
+            "abstract-reference" => Some(Self::AbstractReference),
+            "naming" => Some(Self::Naming),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnusedTemplate => f.write_str("unused-template"),
+            Self::IdenticalOverride => f.write_str("identical-override"),
+            Self::RedundantParent => f.write_str("redundant-parent"),
+            Self::AbstractReference => f.write_str("abstract-reference"),
+            Self::Naming => f.write_str("naming"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    #[default]
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Warning => f.write_str("warning"),
+            Self::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// Case ids are expected to be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Naming {
+    #[default]
+    #[serde(rename = "snake_case")]
+    SnakeCase,
+    #[serde(rename = "camelCase")]
+    CamelCase,
+    #[serde(rename = "PascalCase")]
+    PascalCase,
+}
+
+impl Naming {
+    fn matches(&self, id: &str) -> bool {
+        let first = id.chars().next();
+        match self {
+            Self::SnakeCase => {
+                !id.chars().any(char::is_uppercase)
+                    && !id.starts_with('_')
+                    && !id.ends_with('_')
+                    && !id.contains("__")
+            }
+            Self::CamelCase => !id.contains('_') && first.is_some_and(|c| !c.is_uppercase()),
+            Self::PascalCase => !id.contains('_') && first.is_some_and(char::is_uppercase),
+        }
+    }
+}
+
+impl Display for Naming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SnakeCase => f.write_str("snake_case"),
+            Self::CamelCase => f.write_str("camelCase"),
+            Self::PascalCase => f.write_str("PascalCase"),
+        }
+    }
+}
+
+/// Severities of the rules and their settings, read from a YAML or JSON
+/// file. Rules left out are warnings.
+///
+/// ```yaml
+/// rules:
+///   redundant-parent: error
+///   abstract-reference: off
+/// naming: camelCase
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub rules: HashMap<Rule, Severity>,
+    pub naming: Naming,
+}
+
+impl LintConfig {
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        serde_yaml::from_str(source).map_err(|err| NonError::InvalidDocument(err.to_string()))
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.rules.get(&rule).copied().unwrap_or_default()
+    }
+}
+
+/// A rule broken at some place of the source.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}[{}]: {}",
+            self.span, self.severity, self.rule, self.message
+        )
+    }
+}
+
+/// Runs every rule that is not off over `defs`, parsed from `source`, and
+/// returns what they report in source order.
+///
+/// A `# lint: allow RULE...` comment suppresses the given rules on its line,
+/// or on the next one when it stands alone on its line.
+pub fn lint(defs: &NonDefs, source: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let suppressions = suppressions(source);
+    let mut diagnostics = Vec::new();
+    let mut report = |rule: Rule, span: Span, message: String| {
+        let severity = config.severity(rule);
+        let suppressed = suppressions
+            .get(&span.line)
+            .is_some_and(|rules| rules.contains(&rule));
+
+        if severity != Severity::Off && !suppressed {
+            diagnostics.push(Diagnostic {
+                rule,
+                severity,
+                span,
+                message,
+            });
+        }
+    };
+
+    let concrete = defs.concrete();
+    let mut inherited = HashSet::new();
+    let mut referenced = HashSet::new();
+    for non in defs.nons() {
+        inherited.extend(non.parents.iter().copied());
+
+        for value in non.own_fields().values() {
+            let mut references = Vec::new();
+            collect_references(value, &mut references);
+            referenced.extend(references.into_iter().map(|(other, _)| other));
+            collect_tests(value, &mut referenced);
+        }
+
+        for &parent in &non.parents {
+            referenced.extend(
+                non.arguments(parent)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|argument| match argument {
+                        Argument::Non(bound) => Some(*bound),
+                        Argument::Param(_) => None,
+                    }),
+            );
+        }
+    }
+
+    for (index, non) in defs.nons().iter().enumerate() {
+        let id = defs.id_of(index);
+        // concrete nons are expected to inherit from a template
+        let is_template =
+            !non.params().is_empty() || !non.schema().is_empty() || non.parents.is_empty();
+
+        if is_template && !inherited.contains(&index) && !referenced.contains(&index) {
+            report(
+                Rule::UnusedTemplate,
+                non.span,
+                format!("Template '{}' is never inherited nor referenced.", id),
+            );
+        }
+
+        for &parent in &non.parents {
+            let through = non
+                .parents
+                .iter()
+                .find(|&&other| other != parent && defs.ancestors(other).contains(&parent));
+
+            if let Some(&through) = through {
+                report(
+                    Rule::RedundantParent,
+                    non.span,
+                    format!(
+                        "'{}' already inherits '{}' through '{}'.",
+                        id,
+                        defs.id_of(parent),
+                        defs.id_of(through)
+                    ),
+                );
+            }
+        }
+
+        if !config.naming.matches(id) {
+            report(
+                Rule::Naming,
+                non.span,
+                format!("Id '{}' is not {}.", id, config.naming),
+            );
+        }
+
+        let mut fields = non.own_fields().iter().collect::<Vec<_>>();
+        fields.sort_by_key(|&(&name, _)| non.field_span(name).map(|span| span.line));
+
+        for (&name, value) in fields {
+            let span = non.field_span(name).unwrap_or(non.span);
+
+            let inherited = non
+                .parents
+                .iter()
+                .rev()
+                .find_map(|&parent| defs.fields_at(parent).get(&name));
+            if inherited == Some(value) && !non.unset_fields().contains(&name) {
+                report(
+                    Rule::IdenticalOverride,
+                    span,
+                    format!(
+                        "Field '{}' of '{}' has the value it already inherits.",
+                        defs.name(name),
+                        id
+                    ),
+                );
+            }
+
+            let mut references = Vec::new();
+            collect_references(value, &mut references);
+            for (other, field) in references {
+                if !concrete.contains(&other) || !defs.non(other).params().is_empty() {
+                    report(
+                        Rule::AbstractReference,
+                        span,
+                        format!(
+                            "Field '{}' of '{}' refers to '{}.{}', which is abstract.",
+                            defs.name(name),
+                            id,
+                            defs.id_of(other),
+                            defs.name(field)
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.column));
+    diagnostics
+}
+
+/// Adds the nons tested by every `is non` found in `value`.
+fn collect_tests(value: &FieldValue, tested: &mut HashSet<NonId>) {
+    match value {
+        FieldValue::Is(non) => {
+            tested.insert(*non);
+        }
+        FieldValue::Vec(values)
+        | FieldValue::Interpolated(values)
+        | FieldValue::Call(_, values) => {
+            for value in values {
+                collect_tests(value, tested);
+            }
+        }
+        FieldValue::Binary(_, left, right) => {
+            collect_tests(left, tested);
+            collect_tests(right, tested);
+        }
+        FieldValue::Neg(operand) => collect_tests(operand, tested),
+        FieldValue::If(condition, then, otherwise) => {
+            collect_tests(condition, tested);
+            collect_tests(then, tested);
+            collect_tests(otherwise, tested);
+        }
+        FieldValue::Litteral(_)
+        | FieldValue::FieldReference(_)
+        | FieldValue::ObjRef(..)
+        | FieldValue::Number(_)
+        | FieldValue::Bool(_)
+        | FieldValue::ParamRef(..) => {}
+    }
+}
+
+/// Finds the `# lint: allow RULE...` comments of `source`, by the line they
+/// apply to.
+fn suppressions(source: &str) -> HashMap<usize, Vec<Rule>> {
+    let mut suppressions = HashMap::<usize, Vec<Rule>>::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let Some(start) = comment_start(line) else {
+            continue;
+        };
+        let Some(names) = line[start + 1..].trim().strip_prefix("lint: allow") else {
+            continue;
+        };
+
+        let line_number = if line[..start].trim().is_empty() {
+            number + 2
+        } else {
+            number + 1
+        };
+
+        suppressions.entry(line_number).or_default().extend(
+            names
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter_map(Rule::from_name),
+        );
+    }
+
+    suppressions
+}
+
+/// Byte position of the `#` starting the comment of `line`, if any, skipping
+/// the ones inside quotes.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (position, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') => return Some(position),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::NonLexer, parser::NonParser};
+
+    fn run(source: &str, config: &LintConfig) -> Vec<(Rule, usize)> {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap();
+
+        lint(&defs, source, config)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.rule, diagnostic.span.line))
+            .collect()
+    }
+
+    #[test]
+    fn unused_templates_are_reported() {
+        let source = "person:
+.name @
+
+school(city):
+.city city.name
+
+account:
+!login required
+
+alice: person
+";
+
+        assert_eq!(
+            run(source, &LintConfig::default()),
+            [(Rule::UnusedTemplate, 4), (Rule::UnusedTemplate, 7)]
+        );
+        assert!(
+            run(
+                "person:\n.name @\n\nalice: person\n",
+                &LintConfig::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn identical_overrides_are_reported() {
+        let source = "person:\n.kind 'human'\n\nalice: person\n.kind 'human'\n\nbob: person\n.kind 'robot'\n";
+
+        assert_eq!(
+            run(source, &LintConfig::default()),
+            [(Rule::IdenticalOverride, 5)]
+        );
+    }
+
+    #[test]
+    fn redundant_parents_are_reported() {
+        let source = "a:\n.x 1\n\nb: a\n\nc: a b\n";
+
+        assert_eq!(
+            run(source, &LintConfig::default()),
+            [(Rule::RedundantParent, 6)]
+        );
+    }
+
+    #[test]
+    fn abstract_references_are_reported() {
+        let source = "univ:\n.domain 'exemple.tld'\n\nmit: univ\n\nstudent:\n.mail univ.domain\n.site mit.domain\n\nalice: student\n";
+
+        assert_eq!(
+            run(source, &LintConfig::default()),
+            [(Rule::AbstractReference, 7)]
+        );
+    }
+
+    #[test]
+    fn naming_is_configurable() {
+        let source = "person:\n.x 1\n\naliceDoe: person\n\nbob_doe: person\n";
+
+        assert_eq!(run(source, &LintConfig::default()), [(Rule::Naming, 4)]);
+
+        let config = LintConfig::from_yaml("naming: camelCase").unwrap();
+        assert_eq!(run(source, &config), [(Rule::Naming, 6)]);
+    }
+
+    #[test]
+    fn config_sets_severities() {
+        let config =
+            LintConfig::from_yaml("rules:\n  naming: error\n  redundant-parent: off\n").unwrap();
+
+        assert_eq!(config.severity(Rule::Naming), Severity::Error);
+        assert_eq!(config.severity(Rule::RedundantParent), Severity::Off);
+        assert_eq!(config.severity(Rule::UnusedTemplate), Severity::Warning);
+        assert_eq!(config.naming, Naming::SnakeCase);
+        assert!(run("a:\n.x 1\n\nb: a\n\nc: a b\n", &config).is_empty());
+
+        let json = LintConfig::from_yaml(r#"{"rules": {"naming": "off"}, "naming": "PascalCase"}"#)
+            .unwrap();
+        assert_eq!(json.severity(Rule::Naming), Severity::Off);
+        assert_eq!(json.naming, Naming::PascalCase);
+
+        assert!(LintConfig::from_yaml("rules:\n  unknown: error\n").is_err());
+        assert!(LintConfig::from_yaml("severity: error\n").is_err());
+    }
+
+    #[test]
+    fn comments_suppress_rules() {
+        let source = "a:\n.x 1\n\n# lint: allow redundant-parent\nc: a b\n\nb: a\n\nd: a b # lint: allow naming, redundant-parent\n\ne: a b # lint: allow naming\n";
+
+        assert_eq!(
+            run(source, &LintConfig::default()),
+            [(Rule::RedundantParent, 11)]
+        );
+        assert_eq!(
+            suppressions("# lint: allow naming unknown\na: # lint: allow identical-override"),
+            HashMap::from([(2, vec![Rule::Naming, Rule::IdenticalOverride])])
+        );
+    }
+
+    #[test]
+    fn comments_start_outside_quotes() {
+        assert_eq!(comment_start(".x 'a' # b"), Some(7));
+        assert_eq!(comment_start(".x 'a#b'"), None);
+        assert_eq!(comment_start(".x \"it's #1\" # b"), Some(13));
+        assert_eq!(comment_start("# lint: allow naming"), Some(0));
+        assert_eq!(comment_start(".x 'a'"), None);
+    }
+}
@@ -1,46 +1,283 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{fs, path::Path, process};
 
 use clap::Parser;
 
-use crate::{args::Args, lexer::NonLexer, nds::NonDefs, parser::NonParser};
-
-mod args;
-mod error;
-mod lexer;
-mod nds;
-mod non;
-mod parser;
-mod token;
+use non::{
+    args::{
+        Args, CheckArgs, CodegenCommand, Command, ConvertArgs, DiffArgs, ExtractParentArgs,
+        GraphArgs, ImportCsvArgs, LintArgs, OutputFormat, RefactorCommand, RustArgs, SandboxArgs,
+    },
+    codegen,
+    csv::CsvOptions,
+    diff,
+    error::{NonError, Result},
+    graph::{self, GraphOptions},
+    import,
+    ldif::LdifOptions,
+    lexer::NonLexer,
+    lint::{self, LintConfig, Severity},
+    nds::{NonDefs, NonDefsBuilder, Sandbox},
+    parser::NonParser,
+    schema,
+};
 
 fn main() {
     let args = Args::parse();
-    let path = Path::new(&args.path);
 
-    if path.exists() {
-        let mut file = File::open(path).unwrap();
-        let mut buf = String::default();
+    let result = match args.command {
+        Command::Convert(args) => convert(args),
+        Command::Check(args) => check(args),
+        Command::ImportCsv(args) => import_csv(args),
+        Command::Diff(args) => diff(args),
+        Command::Refactor(args) => match args.command {
+            RefactorCommand::ExtractParent(args) => extract_parent(args),
+        },
+        Command::Codegen(args) => match args.command {
+            CodegenCommand::Rust(args) => codegen_rust(args),
+        },
+        Command::Graph(args) => graph(args),
+        Command::Lint(args) => lint(args),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Changes applied on top of a loaded file, before anything is resolved.
+#[derive(Default)]
+struct Overrides<'a> {
+    overlays: &'a [String],
+    sets: &'a [(String, String, String)],
+    allow_new: bool,
+}
+
+/// Reads a `.non` file, or a JSON or YAML document whose values are litterals,
+/// or `.non` source when `raw`, then applies the `.non` overlay files and the
+/// `--set` assignments in order. Relative `file()` paths are read from the
+/// directory of `path`.
+fn load(path: &str, raw: bool, overrides: &Overrides) -> Result<NonDefsBuilder> {
+    let mut buf = fs::read_to_string(path)?;
+    let imported = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Some(import::from_json(&buf, raw)?),
+        Some("yaml" | "yml") => Some(import::from_yaml(&buf, raw)?),
+        _ => None,
+    };
+
+    if let Some(nons) = imported {
+        buf = nons
+            .iter()
+            .map(|non| non.serialize_non())
+            .collect::<Result<Vec<_>>>()?
+            .join("\n");
+    }
+
+    let overlays = overrides
+        .overlays
+        .iter()
+        .map(fs::read_to_string)
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let lexer = NonLexer::new(&buf);
+    let mut parser = NonParser::new(lexer);
+
+    parser.parse()?;
+    for overlay in &overlays {
+        parser.parse_overlay(NonLexer::new(overlay), overrides.allow_new)?;
+    }
+
+    for (id, field, value) in overrides.sets {
+        parser.parse_assignment(id, field, value, overrides.allow_new)?;
+    }
+
+    Ok(NonDefs::builder()
+        .nons(parser.nons)
+        .interner(parser.interner)
+        .base_dir(Path::new(path).parent().unwrap_or(Path::new(""))))
+}
+
+fn convert(args: ConvertArgs) -> Result<()> {
+    let non_defs = load(
+        &args.path,
+        args.raw,
+        &Overrides {
+            overlays: &args.overlays,
+            sets: &args.sets,
+            allow_new: args.allow_new,
+        },
+    )?
+    .format(args.format)
+    .flat(args.flat)
+    .ldif(LdifOptions {
+        dn: args.dn,
+        object_classes: args.object_classes,
+    })
+    .csv(CsvOptions {
+        columns: args.columns,
+        ancestor: args.descendants_of,
+    })
+    .sandbox(sandbox(&args.sandbox))
+    .build()?;
+
+    write(args.output, non_defs.serialize()?)
+}
+
+fn sandbox(args: &SandboxArgs) -> Sandbox {
+    Sandbox {
+        deny_env: args.deny_env,
+        deny_file: args.deny_file,
+    }
+}
+
+fn write(output: Option<String>, content: String) -> Result<()> {
+    match output {
+        Some(output) => fs::write(output, content)?,
+        None => println!("{}", content),
+    }
+
+    Ok(())
+}
+
+fn check(args: CheckArgs) -> Result<()> {
+    let non_defs = load(&args.path, false, &Overrides::default())?
+        .sandbox(sandbox(&args.sandbox))
+        .build()?;
+    let violations = schema::check(&non_defs)?;
 
-        file.read_to_string(&mut buf).unwrap();
-        let lexer = NonLexer::new(&buf);
-        let mut parser = NonParser::new(lexer);
+    for violation in &violations {
+        println!("{}:{}", args.path, violation);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(NonError::CheckFailed(violations.len()))
+    }
+}
+
+fn diff(args: DiffArgs) -> Result<()> {
+    let old = load(&args.old, false, &Overrides::default())?
+        .sandbox(sandbox(&args.sandbox))
+        .build()?;
+    let new = load(&args.new, false, &Overrides::default())?
+        .sandbox(sandbox(&args.sandbox))
+        .build()?;
+    let diff = diff::diff(&old, &new, args.raw)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+    } else {
+        print!("{}", diff);
+    }
 
-        parser.parse();
-        let non_defs = NonDefs::builder()
-            .format(args.format)
-            .nons(parser.nons)
-            .flat(args.flat)
-            .build();
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(NonError::DiffFound(diff.len()))
+    }
+}
 
-        // let content = non_defs.serialize();
-        // println!("{}", content);
+fn lint(args: LintArgs) -> Result<()> {
+    let config = match &args.config {
+        Some(config) => LintConfig::from_yaml(&fs::read_to_string(config)?)?,
+        None => LintConfig::default(),
+    };
+    let source = fs::read_to_string(&args.path)?;
+    let non_defs = load(&args.path, false, &Overrides::default())?.build()?;
+    let diagnostics = lint::lint(&non_defs, &source, &config);
 
-        // let alice = non_defs.at("alice").unwrap();
-        // let bob = non_defs.at("bob").unwrap();
-        // println!("alice.mail {}", alice.get("mail").unwrap());
+    for diagnostic in &diagnostics {
+        println!("{}:{}", args.path, diagnostic);
+    }
 
-        let b = non_defs.at("b").unwrap();
-        let c = non_defs.at("c").unwrap();
-        let union = b.union(c);
-        println!("union : {:?}", union);
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors == 0 {
+        Ok(())
+    } else {
+        Err(NonError::LintFailed(errors))
     }
 }
+
+fn import_csv(args: ImportCsvArgs) -> Result<()> {
+    let source = fs::read_to_string(&args.path)?;
+    let nons = import::from_csv(&source, args.delimiter, &args.id_column, &args.parent)?;
+
+    let content = match args.defs {
+        Some(defs) => {
+            let mut non_defs = load(&defs, false, &Overrides::default())?
+                .format(args.format.unwrap_or(OutputFormat::Non))
+                .flat(args.flat)
+                .sandbox(sandbox(&args.sandbox))
+                .build()?;
+
+            for non in &nons {
+                non.add_to(&mut non_defs)?;
+            }
+
+            non_defs.serialize()?
+        }
+        None => nons
+            .iter()
+            .map(|non| non.serialize_non())
+            .collect::<Result<Vec<_>>>()?
+            .join("\n"),
+    };
+
+    write(args.output, content)
+}
+
+fn extract_parent(args: ExtractParentArgs) -> Result<()> {
+    let mut non_defs = load(&args.path, false, &Overrides::default())?.build()?;
+    let before = non_defs.serialize()?.lines().count();
+
+    let children = args.nons.iter().map(String::as_str).collect::<Vec<_>>();
+    let fields = non_defs.extract_parent(&args.name, &children)?;
+
+    let content = non_defs.serialize()?;
+    let saved = before as isize - content.lines().count() as isize;
+    eprintln!(
+        "Extracted {} field(s) into '{}', {} line(s) saved.",
+        fields.len(),
+        args.name,
+        saved
+    );
+
+    write(args.output, content)
+}
+
+fn codegen_rust(args: RustArgs) -> Result<()> {
+    let non_defs = load(&args.path, false, &Overrides::default())?.build()?;
+
+    let nons = if args.nons.is_empty() {
+        let concrete = non_defs.concrete();
+        (0..non_defs.nons().len())
+            .filter(|index| !concrete.contains(index))
+            .collect()
+    } else {
+        args.nons
+            .iter()
+            .map(|id| {
+                non_defs
+                    .index_of(id)
+                    .ok_or_else(|| NonError::UnknownNon(id.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    write(args.output, codegen::rust(&non_defs, &nons, args.flat)?)
+}
+
+fn graph(args: GraphArgs) -> Result<()> {
+    let non_defs = load(&args.path, false, &Overrides::default())?.build()?;
+    let options = GraphOptions {
+        format: args.format,
+        ancestors_of: args.ancestors_of,
+        descendants_of: args.descendants_of,
+    };
+
+    write(args.output, graph::serialize(&non_defs, &options)?)
+}
@@ -1,84 +1,1078 @@
 use std::{
-    cell::{Ref, RefCell},
-    collections::HashMap,
-    rc::Rc,
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+    thread,
 };
 
-use crate::{args::OutputFormat, non::Non};
+use crate::{
+    args::OutputFormat,
+    csv::{self, CsvOptions},
+    error::{NonError, Result},
+    function::{self, Function},
+    interner::{Interner, Symbol},
+    json_schema,
+    ldif::{self, LdifOptions},
+    non::{Argument, FieldValue, Non, NonId, litteral},
+    operator,
+    schema::{FieldSchema, ValueType, json_value},
+    typescript,
+};
 
+/// Frozen set of definitions produced once parsing is done.
+///
+/// Nons live in an arena and refer to each other by [`NonId`], so the whole
+/// structure is plain owned data and can be shared between threads.
 pub struct NonDefs {
-    nons: HashMap<String, Rc<RefCell<Non>>>,
+    nons: Vec<Non>,
+    index: HashMap<Symbol, NonId>,
+    interner: Interner,
     format: OutputFormat,
     flat: bool,
+    ldif: LdifOptions,
+    csv: CsvOptions,
+    sandbox: Sandbox,
+    /// Directory relative `file()` paths are read from.
+    base_dir: PathBuf,
+    cache: ResolutionCache,
+}
+
+/// Sources outside the definitions that field values are not allowed to read,
+/// for untrusted inputs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sandbox {
+    pub deny_env: bool,
+    pub deny_file: bool,
+}
+
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<NonDefs>();
+};
+
+/// Memoised resolution results, one slot per non of the arena.
+///
+/// Entries are computed lazily the first time a non is looked up and dropped
+/// as a whole whenever a definition is mutated through [`NonDefs`].
+struct ResolutionCache {
+    fields: Vec<OnceLock<HashMap<Symbol, FieldValue>>>,
+    values: Vec<RwLock<HashMap<Symbol, String>>>,
+}
+
+impl ResolutionCache {
+    fn new(len: usize) -> Self {
+        Self {
+            fields: (0..len).map(|_| OnceLock::new()).collect(),
+            values: (0..len).map(|_| RwLock::default()).collect(),
+        }
+    }
 }
 
 impl NonDefs {
-    pub fn new(nons: HashMap<String, Rc<RefCell<Non>>>, format: OutputFormat, flat: bool) -> Self {
-        NonDefs { nons, format, flat }
+    pub fn new(nons: Vec<Non>, interner: Interner, format: OutputFormat, flat: bool) -> Self {
+        let index = nons
+            .iter()
+            .enumerate()
+            .map(|(index, non)| (non.id(), index))
+            .collect();
+        let cache = ResolutionCache::new(nons.len());
+
+        NonDefs {
+            nons,
+            index,
+            interner,
+            format,
+            flat,
+            ldif: LdifOptions::default(),
+            csv: CsvOptions::default(),
+            sandbox: Sandbox::default(),
+            base_dir: PathBuf::new(),
+            cache,
+        }
     }
 
     pub fn builder() -> NonDefsBuilder {
         NonDefsBuilder::default()
     }
 
-    pub fn at(&self, id: &str) -> Option<Ref<'_, Non>> {
-        self.nons.get(id).map(|n| n.borrow())
+    pub fn at(&self, id: &str) -> Option<&Non> {
+        self.index_of(id).map(|index| &self.nons[index])
+    }
+
+    pub fn index_of(&self, id: &str) -> Option<NonId> {
+        self.index.get(&self.interner.get(id)?).copied()
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    /// Returns the string behind an interned identifier or field name.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        self.interner.resolve(symbol)
+    }
+
+    /// Returns the id of the non at `index`.
+    pub fn id_of(&self, index: NonId) -> &str {
+        self.name(self.nons[index].id())
+    }
+
+    pub fn non(&self, index: NonId) -> &Non {
+        &self.nons[index]
+    }
+
+    pub fn nons(&self) -> &[Non] {
+        &self.nons
+    }
+
+    /// Returns every field of `id`, inherited ones included, without resolving
+    /// references.
+    pub fn fields(&self, id: &str) -> Option<&HashMap<Symbol, FieldValue>> {
+        self.index_of(id).map(|index| self.fields_at(index))
+    }
+
+    pub(crate) fn fields_at(&self, index: NonId) -> &HashMap<Symbol, FieldValue> {
+        self.cache.fields[index].get_or_init(|| {
+            let non = &self.nons[index];
+            let mut map = HashMap::new();
+
+            for &parent in &non.parents {
+                map.extend(
+                    self.fields_at(parent)
+                        .iter()
+                        .map(|(name, value)| (*name, value.clone())),
+                );
+            }
+
+            map.retain(|name, _| !non.unset_fields().contains(name));
+            map.extend(non.own_fields().clone());
+            map
+        })
+    }
+
+    /// Returns the index of the non whose declaration provides `field` to the
+    /// non at `index`, following the same precedence as [`NonDefs::fields`].
+    pub fn field_origin(&self, index: NonId, field: Symbol) -> Option<NonId> {
+        let non = &self.nons[index];
+
+        if non.own_fields().contains_key(&field) {
+            return Some(index);
+        }
+
+        if non.unset_fields().contains(&field) {
+            return None;
+        }
+
+        non.parents
+            .iter()
+            .rev()
+            .find_map(|&parent| self.field_origin(parent, field))
+    }
+
+    /// Returns the field schemas of the non at `index`, merged with the ones
+    /// of its ancestors.
+    pub fn schema_at(&self, index: NonId) -> HashMap<Symbol, FieldSchema> {
+        let non = &self.nons[index];
+        let mut schema = HashMap::<Symbol, FieldSchema>::new();
+
+        for &parent in &non.parents {
+            for (name, field_schema) in self.schema_at(parent) {
+                schema
+                    .entry(name)
+                    .and_modify(|existing| existing.merge(&field_schema))
+                    .or_insert(field_schema);
+            }
+        }
+
+        for (&name, field_schema) in non.schema() {
+            schema
+                .entry(name)
+                .and_modify(|existing| existing.merge(field_schema))
+                .or_insert_with(|| field_schema.clone());
+        }
+
+        schema
+    }
+
+    /// Returns every ancestor of the non at `index`, nearest parents first and
+    /// each one once.
+    pub fn ancestors(&self, index: NonId) -> Vec<NonId> {
+        let mut ancestors = Vec::new();
+        let mut queue = self.nons[index].parents.clone();
+
+        while !queue.is_empty() {
+            let parent = queue.remove(0);
+            if !ancestors.contains(&parent) {
+                ancestors.push(parent);
+                queue.extend(self.nons[parent].parents.iter().copied());
+            }
+        }
+
+        ancestors
     }
 
-    pub fn serialize(&self) -> String {
+    /// Returns the type of the resolved fields of the non at `index` that
+    /// have one: the type declared by their schema, or else the one of their
+    /// value, as told by [`FieldValue::value_type`].
+    pub fn value_types(&self, index: NonId) -> HashMap<Symbol, ValueType> {
+        let mut value_types = self
+            .schema_at(index)
+            .into_iter()
+            .filter_map(|(name, field_schema)| Some((name, field_schema.value_type?)))
+            .collect::<HashMap<_, _>>();
+
+        for &name in self.fields_at(index).keys() {
+            if !value_types.contains_key(&name)
+                && let Some(value_type) = self.value_type(index, name, &mut Vec::new())
+            {
+                value_types.insert(name, value_type);
+            }
+        }
+
+        value_types
+    }
+
+    /// Returns the type each field of every non is exported with, by index.
+    ///
+    /// A field shares its type with the same field of the ancestors and
+    /// descendants declaring it, so that it is exported the same way by a
+    /// template and all of the nons inheriting from it. When their values do
+    /// not agree, the field has no type and is exported as text everywhere.
+    /// Templates taking parameters are not exported and do not take part.
+    pub fn field_types(&self) -> Vec<HashMap<Symbol, ValueType>> {
+        // one slot per field of each non, slots of a same field being merged
+        // along inheritance
+        let mut slots = Vec::with_capacity(self.nons.len());
+        let mut merged = Vec::new();
+        let mut types = Vec::new();
+
+        for index in 0..self.nons.len() {
+            let value_types = self.value_types(index);
+            let exported = self.nons[index].params().is_empty();
+            let mut non_slots = HashMap::new();
+
+            for &name in self.fields_at(index).keys().chain(value_types.keys()) {
+                non_slots.entry(name).or_insert_with(|| {
+                    merged.push(merged.len());
+                    types.push(exported.then(|| value_types.get(&name).copied()));
+                    merged.len() - 1
+                });
+            }
+            slots.push(non_slots);
+        }
+
+        fn root(merged: &mut [usize], mut slot: usize) -> usize {
+            while merged[slot] != slot {
+                merged[slot] = merged[merged[slot]];
+                slot = merged[slot];
+            }
+            slot
+        }
+
+        for index in 0..self.nons.len() {
+            for ancestor in self.ancestors(index) {
+                for (name, &slot) in &slots[index] {
+                    if let Some(&other) = slots[ancestor].get(name) {
+                        let (slot, other) = (root(&mut merged, slot), root(&mut merged, other));
+                        merged[slot] = other;
+                    }
+                }
+            }
+        }
+
+        // `None` while no exported non has a value, `Some(None)` on conflicts
+        let mut agreed = vec![None; merged.len()];
+        for (slot, value_type) in types.into_iter().enumerate() {
+            let Some(value_type) = value_type else {
+                continue;
+            };
+            let root = root(&mut merged, slot);
+            agreed[root] = match agreed[root] {
+                None => Some(value_type),
+                Some(other) if other == value_type => Some(other),
+                Some(_) => Some(None),
+            };
+        }
+
+        slots
+            .into_iter()
+            .map(|non_slots| {
+                non_slots
+                    .into_iter()
+                    .filter_map(|(name, slot)| {
+                        Some((name, agreed[root(&mut merged, slot)].flatten()?))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Type of the field `name` of the non at `index`, following references
+    /// to other fields. `visiting` holds the fields being typed, a reference
+    /// cycle having no type.
+    fn value_type(
+        &self,
+        index: NonId,
+        name: Symbol,
+        visiting: &mut Vec<(NonId, Symbol)>,
+    ) -> Option<ValueType> {
+        if visiting.contains(&(index, name)) {
+            return None;
+        }
+
+        let value = self.fields_at(index).get(&name)?;
+        visiting.push((index, name));
+        let value_type = value.value_type(&mut |reference| {
+            let (non, field) = match *reference {
+                FieldValue::FieldReference(field) if field != Symbol::ID => (index, field),
+                FieldValue::ObjRef(non, field) => (non, field),
+                FieldValue::ParamRef(template, position, field) => {
+                    (self.bind(index, template, position).ok()?, field)
+                }
+                _ => return None,
+            };
+
+            self.schema_at(non)
+                .get(&field)
+                .and_then(|field_schema| field_schema.value_type)
+                .or_else(|| self.value_type(non, field, visiting))
+        });
+        visiting.pop();
+
+        value_type
+    }
+
+    /// Returns the fields of the non at `index` marked `hidden` by its schema
+    /// or the one of its ancestors.
+    pub fn hidden_fields(&self, index: NonId) -> HashSet<Symbol> {
+        self.schema_at(index)
+            .into_iter()
+            .filter(|(_, field_schema)| field_schema.hidden)
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Returns the nons no other non inherits from, in declaration order,
+    /// leaving out templates taking parameters as they cannot be resolved on
+    /// their own.
+    pub fn concrete(&self) -> Vec<NonId> {
+        let parents = self
+            .nons
+            .iter()
+            .flat_map(|non| non.parents.iter().copied())
+            .collect::<HashSet<_>>();
+
+        (0..self.nons.len())
+            .filter(|&index| !parents.contains(&index) && self.nons[index].params().is_empty())
+            .collect()
+    }
+
+    /// Resolves the field `field_name` of `id`, following inheritance and
+    /// references.
+    pub fn get(&self, id: &str, field_name: &str) -> Result<String> {
+        let index = self
+            .index_of(id)
+            .ok_or_else(|| NonError::UnknownNon(id.to_string()))?;
+        let field_name = self
+            .interner
+            .get(field_name)
+            .ok_or_else(|| NonError::UnknownField {
+                non: id.to_string(),
+                field: field_name.to_string(),
+            })?;
+        self.get_at(index, field_name)
+    }
+
+    pub(crate) fn get_at(&self, index: NonId, field_name: Symbol) -> Result<String> {
+        self.resolve_reference(index, field_name, &mut Vec::new())
+    }
+
+    /// Resolves the field `field_name` of the non at `index`, failing when it
+    /// is among the fields of `resolving`, which are being resolved and
+    /// would otherwise refer to each other forever.
+    fn resolve_reference(
+        &self,
+        index: NonId,
+        field_name: Symbol,
+        resolving: &mut Vec<(NonId, Symbol)>,
+    ) -> Result<String> {
+        if let Some(value) = self.cache.values[index].read().unwrap().get(&field_name) {
+            return Ok(value.clone());
+        }
+
+        if let Some(start) = resolving
+            .iter()
+            .position(|&field| field == (index, field_name))
+        {
+            let cycle = resolving[start..]
+                .iter()
+                .chain([&(index, field_name)])
+                .map(|&(non, field)| format!("{}.{}", self.id_of(non), self.name(field)))
+                .collect::<Vec<_>>();
+            return Err(NonError::ReferenceCycle(cycle.join(" -> ")));
+        }
+
+        let field =
+            self.fields_at(index)
+                .get(&field_name)
+                .ok_or_else(|| NonError::UnknownField {
+                    non: self.id_of(index).to_string(),
+                    field: self.name(field_name).to_string(),
+                })?;
+        resolving.push((index, field_name));
+        let value = self.resolve(index, field, resolving);
+        resolving.pop();
+        let value = value?;
+
+        self.cache.values[index]
+            .write()
+            .unwrap()
+            .insert(field_name, value.clone());
+        Ok(value)
+    }
+
+    pub fn resolve_field(&self, index: NonId, field_value: &FieldValue) -> Result<String> {
+        self.resolve(index, field_value, &mut Vec::new())
+    }
+
+    fn resolve(
+        &self,
+        index: NonId,
+        field_value: &FieldValue,
+        resolving: &mut Vec<(NonId, Symbol)>,
+    ) -> Result<String> {
         let mut str = String::new();
+        match field_value {
+            FieldValue::Litteral(v) => str.push_str(v),
+            FieldValue::Vec(field_values) | FieldValue::Interpolated(field_values) => {
+                for field_value in field_values {
+                    str.push_str(&self.resolve(index, field_value, resolving)?);
+                }
+            }
+            FieldValue::FieldReference(reference) => {
+                if *reference == Symbol::ID {
+                    str.push_str(self.id_of(index));
+                } else {
+                    str.push_str(&self.resolve_reference(index, *reference, resolving)?)
+                }
+            }
+            FieldValue::ObjRef(non, field_name) => {
+                str.push_str(&self.resolve_reference(*non, *field_name, resolving)?)
+            }
+            FieldValue::Number(number) => str.push_str(number),
+            FieldValue::Bool(bool) => str.push_str(&bool.to_string()),
+            FieldValue::Binary(operator, left, right) => str.push_str(&operator.apply(
+                &self.resolve(index, left, resolving)?,
+                &self.resolve(index, right, resolving)?,
+            )?),
+            FieldValue::Neg(operand) => str.push_str(&operator::negate(
+                &self.resolve(index, operand, resolving)?,
+            )?),
+            FieldValue::ParamRef(template, position, field) => {
+                let bound = self.bind(index, *template, *position)?;
+                str.push_str(&self.resolve_reference(bound, *field, resolving)?)
+            }
+            FieldValue::Is(non) => {
+                let is = index == *non || self.ancestors(index).contains(non);
+                str.push_str(&is.to_string())
+            }
+            FieldValue::If(condition, then, otherwise) => {
+                let branch = match self.resolve(index, condition, resolving)?.as_str() {
+                    "true" => then,
+                    "false" => otherwise,
+                    value => return Err(NonError::NotABoolean(value.to_string())),
+                };
+                str.push_str(&self.resolve(index, branch, resolving)?)
+            }
+            FieldValue::Call(function, args) => {
+                str.push_str(&self.call(index, *function, args, resolving)?)
+            }
+        }
+        Ok(str)
+    }
+
+    /// Finds the non bound to the parameter at `position` of `template`, as
+    /// given by the nearest of the non at `index` and its ancestors passing
+    /// arguments to `template`.
+    fn bind(&self, index: NonId, template: NonId, position: usize) -> Result<NonId> {
+        let params = self.nons[template].params();
+        let mut lineage = vec![index];
+        lineage.extend(self.ancestors(index));
+
+        for non in lineage {
+            let Some(arguments) = self.nons[non].arguments(template) else {
+                continue;
+            };
+
+            if arguments.len() != params.len() {
+                return Err(NonError::TemplateArity {
+                    span: self.nons[non].span,
+                    template: self.id_of(template).to_string(),
+                    expected: params.len(),
+                    found: arguments.len(),
+                });
+            }
+
+            return match arguments[position] {
+                Argument::Non(bound) => Ok(bound),
+                Argument::Param(forwarded) => self.bind(index, non, forwarded),
+            };
+        }
+
+        Err(NonError::UnboundParameter {
+            template: self.id_of(template).to_string(),
+            parameter: self.name(params[position]).to_string(),
+        })
+    }
+
+    /// Applies `function` to `args`, resolved against the non at `index`.
+    /// Defaults of `env` and `file` are only resolved when needed.
+    fn call(
+        &self,
+        index: NonId,
+        function: Function,
+        args: &[FieldValue],
+        resolving: &mut Vec<(NonId, Symbol)>,
+    ) -> Result<String> {
+        let mut arg = |i: usize| self.resolve(index, &args[i], resolving);
+
+        match function {
+            Function::Lower => Ok(arg(0)?.to_lowercase()),
+            Function::Upper => Ok(arg(0)?.to_uppercase()),
+            Function::Trim => Ok(arg(0)?.trim().to_string()),
+            Function::Replace => Ok(arg(0)?.replace(&arg(1)?, &arg(2)?)),
+            Function::Substr => {
+                let len = args.get(2).map(|_| arg(2)).transpose()?;
+                function::substr(&arg(0)?, &arg(1)?, len.as_deref())
+            }
+            Function::Join => {
+                let items = match &args[0] {
+                    FieldValue::Vec(items) => items.as_slice(),
+                    item => std::slice::from_ref(item),
+                };
+                let items = items
+                    .iter()
+                    .map(|item| self.resolve(index, item, resolving))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(items.join(&self.resolve(index, &args[1], resolving)?))
+            }
+            Function::Slug => Ok(function::slug(&arg(0)?)),
+            Function::Env => match self.read_env(&arg(0)?)? {
+                Some(value) => Ok(value),
+                None if args.len() > 1 => arg(1),
+                None => Err(NonError::MissingEnv(arg(0)?)),
+            },
+            Function::File => match self.read_file(&arg(0)?) {
+                Err(NonError::UnreadableFile { .. }) if args.len() > 1 => arg(1),
+                result => result,
+            },
+        }
+    }
+
+    fn read_env(&self, name: &str) -> Result<Option<String>> {
+        if self.sandbox.deny_env {
+            return Err(NonError::EnvDenied(name.to_string()));
+        }
+
+        Ok(env::var(name).ok())
+    }
+
+    /// Reads the file at `path`, without its final line break. A relative
+    /// `path` is read from the base directory, the one of the `.non` file
+    /// when loaded by the CLI, rather than from the working directory.
+    fn read_file(&self, path: &str) -> Result<String> {
+        if self.sandbox.deny_file {
+            return Err(NonError::FileDenied(path.to_string()));
+        }
+
+        match fs::read_to_string(self.base_dir.join(path)) {
+            Ok(content) => {
+                let content = content.strip_suffix('\n').unwrap_or(&content);
+                Ok(content.strip_suffix('\r').unwrap_or(content).to_string())
+            }
+            Err(err) => Err(NonError::UnreadableFile {
+                path: path.to_string(),
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    /// Writes `field_value` back the way it appears in a `.non` file.
+    pub fn value_to_string(&self, field_value: &FieldValue) -> String {
+        match field_value {
+            // parsed and imported litterals are checked when created
+            FieldValue::Litteral(str) => litteral(str).unwrap(),
+            FieldValue::Vec(field_values) => field_values
+                .iter()
+                .enumerate()
+                .map(|(i, f)| match f {
+                    // the else branch would swallow the values after it
+                    FieldValue::If(..) if i + 1 < field_values.len() => {
+                        format!("({})", self.value_to_string(f))
+                    }
+                    f => self.value_to_string(f),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            FieldValue::FieldReference(reference) => {
+                if *reference == Symbol::ID {
+                    "@".to_string()
+                } else {
+                    format!(".{}", self.name(*reference))
+                }
+            }
+            FieldValue::ObjRef(reference, field) => {
+                format!("{}.{}", self.id_of(*reference), self.name(*field))
+            }
+            FieldValue::Interpolated(parts) => {
+                let parts = parts
+                    .iter()
+                    .map(|part| match part {
+                        FieldValue::Litteral(text) => text.replace('{', "{{").replace('}', "}}"),
+                        part => format!("{{{}}}", self.value_to_string(part)),
+                    })
+                    .collect::<String>();
+                format!("\"{}\"", parts)
+            }
+            FieldValue::Number(number) => number.clone(),
+            FieldValue::Bool(bool) => bool.to_string(),
+            FieldValue::Binary(operator, left, right) => format!(
+                "{} {} {}",
+                self.operand_to_string(left, operator.precedence(), false),
+                operator,
+                self.operand_to_string(right, operator.precedence(), true)
+            ),
+            FieldValue::Neg(operand) => {
+                format!("-{}", self.operand_to_string(operand, u8::MAX, false))
+            }
+            FieldValue::ParamRef(template, position, field) => format!(
+                "{}.{}",
+                self.name(self.nons[*template].params()[*position]),
+                self.name(*field)
+            ),
+            FieldValue::Is(non) => format!("is {}", self.id_of(*non)),
+            FieldValue::If(condition, then, otherwise) => format!(
+                "if {} then {} else {}",
+                self.value_to_string(condition),
+                self.value_to_string(then),
+                self.value_to_string(otherwise)
+            ),
+            FieldValue::Call(function, args) => format!(
+                "{}({})",
+                function,
+                args.iter()
+                    .map(|arg| self.value_to_string(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Writes an operand of an operator of `precedence`, parenthesized when
+    /// it would otherwise be parsed differently.
+    fn operand_to_string(&self, operand: &FieldValue, precedence: u8, right: bool) -> String {
+        let grouped = match operand {
+            FieldValue::Vec(_) | FieldValue::If(..) => true,
+            FieldValue::Binary(operator, _, _) => {
+                operator.precedence() < precedence || (right && operator.precedence() == precedence)
+            }
+            _ => false,
+        };
+
+        if grouped {
+            format!("({})", self.value_to_string(operand))
+        } else {
+            self.value_to_string(operand)
+        }
+    }
+
+    /// Replaces or adds a field on `id`, invalidating every cached resolution.
+    pub fn set_field(&mut self, id: &str, name: &str, value: FieldValue) -> Option<()> {
+        let index = self.index_of(id)?;
+        let name = self.interner.intern(name);
+        self.nons[index].add_field(name, value);
+        self.cache = ResolutionCache::new(self.nons.len());
+        Some(())
+    }
+
+    /// Appends a non inheriting from `parents`, invalidating every cached
+    /// resolution.
+    pub fn add_non(
+        &mut self,
+        id: &str,
+        parents: &[&str],
+        fields: Vec<(&str, FieldValue)>,
+    ) -> Result<NonId> {
+        if self.index_of(id).is_some() {
+            return Err(NonError::DuplicatedNon(id.to_string()));
+        }
+
+        let parents = parents
+            .iter()
+            .map(|&parent| {
+                self.index_of(parent)
+                    .ok_or_else(|| NonError::UnknownNon(parent.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let index = self.nons.len();
+        let symbol = self.interner.intern(id);
+        let mut non = Non::new(symbol, HashMap::new(), parents);
+
+        for (name, value) in fields {
+            non.add_field(self.interner.intern(name), value);
+        }
+
+        self.nons.push(non);
+        self.index.insert(symbol, index);
+        self.cache = ResolutionCache::new(self.nons.len());
+        Ok(index)
+    }
+
+    /// Moves the fields that every non of `children` declares with the same
+    /// value into a new non `id`, appended to their parents. Being last, it
+    /// takes precedence over the other parents, but only for the moved
+    /// fields, which the children used to override anyway. Returns the moved
+    /// fields.
+    ///
+    /// `children` must name at least two distinct nons.
+    pub fn extract_parent(&mut self, id: &str, children: &[&str]) -> Result<Vec<Symbol>> {
+        for (position, child) in children.iter().enumerate() {
+            if children[..position].contains(child) {
+                return Err(NonError::DuplicatedChild(child.to_string()));
+            }
+        }
+
+        if children.len() < 2 {
+            return Err(NonError::TooFewChildren);
+        }
+
+        let children = children
+            .iter()
+            .map(|&child| {
+                self.index_of(child)
+                    .ok_or_else(|| NonError::UnknownNon(child.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (&first, others) = children.split_first().ok_or(NonError::NoCommonField)?;
+        let mut common = self.nons[first]
+            .own_fields()
+            .iter()
+            .filter(|(name, value)| {
+                others
+                    .iter()
+                    .all(|&other| self.nons[other].own_fields().get(name) == Some(value))
+            })
+            .map(|(name, value)| (*name, value.clone()))
+            .collect::<Vec<_>>();
+
+        if common.is_empty() {
+            return Err(NonError::NoCommonField);
+        }
+
+        common.sort_by_key(|(name, _)| self.name(*name).to_string());
+        let names = common.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let fields = common
+            .into_iter()
+            .map(|(name, value)| (self.name(name).to_string(), value))
+            .collect::<Vec<_>>();
+        let parent = self.add_non(
+            id,
+            &[],
+            fields
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone()))
+                .collect(),
+        )?;
+
+        for child in children {
+            for name in &names {
+                self.nons[child].remove_field(*name);
+            }
+            self.nons[child].parents.push(parent);
+        }
+
+        self.cache = ResolutionCache::new(self.nons.len());
+        Ok(names)
+    }
+
+    /// Merges the fields of `a` and `b` into a new non named after `a`.
+    pub fn union(&self, a: &str, b: &str) -> Result<Non> {
+        let non = self
+            .at(a)
+            .ok_or_else(|| NonError::UnknownNon(a.to_string()))?;
+        let other = self
+            .at(b)
+            .ok_or_else(|| NonError::UnknownNon(b.to_string()))?;
+        let fields = self.fields(a).unwrap();
+        let other_fields = self.fields(b).unwrap();
+
+        for (name, value) in fields {
+            if let Some(other_value) = other_fields.get(name)
+                && other_value != value
+            {
+                return Err(NonError::DuplicatedField(self.name(*name).to_string()));
+            }
+        }
+
+        let mut union_fields = fields.clone();
+        union_fields.extend(other_fields.clone());
+
+        let mut parents = non.parents.to_vec();
+        parents.extend(other.parents.iter().copied());
+
+        // filter parents to avoid duplications
+        let mut seen = HashSet::new();
+        parents.retain(|p| seen.insert(*p));
+
+        Ok(Non::new(non.id(), union_fields, parents))
+    }
+
+    pub fn serialize(&self) -> Result<String> {
+        let mut str = String::new();
+        // raw values always stay strings
+        let field_types = match self.format {
+            OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Toml if self.flat => {
+                self.field_types()
+            }
+            _ => Vec::new(),
+        };
+
         match self.format {
             OutputFormat::Json => {
                 str.push_str("[\n");
                 let nons = self
-                    .nons
-                    .values()
-                    .map(|n| n.borrow().serialize_json(self.flat))
-                    .collect::<Vec<_>>()
+                    .serialize_each(|index, non, parents, fields| {
+                        let fields = self.json_fields(field_types.get(index), fields);
+                        Ok(non.serialize_json(&self.interner, parents, &fields, self.flat))
+                    })?
                     .join(",\n");
                 str.push_str(&nons);
                 str.push_str("\n]");
             }
             OutputFormat::Yaml => {
                 let yaml = self
-                    .nons
-                    .values()
-                    .map(|n| n.borrow().serialize_yaml(self.flat))
-                    .collect::<Vec<_>>()
+                    .serialize_each(|index, non, parents, fields| {
+                        // JSON scalars are valid YAML values as well
+                        let fields = self.json_fields(field_types.get(index), fields);
+                        Ok(non.serialize_yaml(&self.interner, parents, &fields, self.flat))
+                    })?
                     .join("\n");
                 str.push_str(&yaml);
             }
             OutputFormat::Non => {
                 let nons = self
-                    .nons
-                    .values()
-                    .map(|n| n.borrow().serialize_non(self.flat))
-                    .collect::<Vec<_>>()
+                    .serialize_each(|_, non, parents, fields| {
+                        if self.flat {
+                            let fields = fields
+                                .iter()
+                                .map(|(&name, value)| Ok((name, litteral(value)?)))
+                                .collect::<Result<_>>()?;
+                            Ok(non.serialize_non(&self.interner, parents, &fields, self.flat))
+                        } else {
+                            Ok(non.serialize_non(&self.interner, parents, fields, self.flat))
+                        }
+                    })?
                     .join("\n");
                 str.push_str(&nons);
             }
+            OutputFormat::Toml => {
+                let tables = self
+                    .serialize_each(|index, non, parents, fields| {
+                        // JSON scalars are valid TOML values as well
+                        let fields = self.json_fields(field_types.get(index), fields);
+                        Ok(non.serialize_toml(&self.interner, parents, &fields, self.flat))
+                    })?
+                    .join("\n");
+                str.push_str(&tables);
+            }
+            OutputFormat::Ldif => str.push_str(&ldif::serialize(self, &self.ldif)?),
+            OutputFormat::Csv => str.push_str(&csv::serialize(self, &self.csv, ',')?),
+            OutputFormat::Tsv => str.push_str(&csv::serialize(self, &self.csv, '\t')?),
+            OutputFormat::JsonSchema => str.push_str(&json_schema::serialize(self, self.flat)?),
+            OutputFormat::Typescript => str.push_str(&typescript::serialize(self, self.flat)?),
+        }
+        Ok(str)
+    }
+
+    /// Serializes every non with `f`, spreading the work over the available
+    /// cores while keeping the declaration order.
+    fn serialize_each<F>(&self, f: F) -> Result<Vec<String>>
+    where
+        F: Fn(NonId, &Non, &[&str], &HashMap<&str, String>) -> Result<String> + Sync,
+    {
+        let serialize = |index: NonId| {
+            let non = &self.nons[index];
+            let parents = non
+                .parents
+                .iter()
+                .map(|&parent| self.parent_to_string(index, parent))
+                .collect::<Vec<_>>();
+            let parents = parents.iter().map(String::as_str).collect::<Vec<_>>();
+            f(index, non, &parents, &self.serialized_fields(index)?)
+        };
+
+        // templates taking parameters cannot be resolved on their own
+        let indices = (0..self.nons.len())
+            .filter(|&index| !self.flat || self.nons[index].params().is_empty())
+            .collect::<Vec<_>>();
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = indices.len().div_ceil(workers).max(1);
+
+        thread::scope(|scope| {
+            indices
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(|| chunk.iter().map(|&i| serialize(i)).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    /// Writes `parent` as it appears in the declaration of the non at `index`,
+    /// with the arguments given to it.
+    pub(crate) fn parent_to_string(&self, index: NonId, parent: NonId) -> String {
+        let non = &self.nons[index];
+        let Some(arguments) = non.arguments(parent) else {
+            return self.id_of(parent).to_string();
+        };
+
+        let arguments = arguments
+            .iter()
+            .map(|argument| match argument {
+                Argument::Non(bound) => self.id_of(*bound),
+                Argument::Param(position) => self.name(non.params()[*position]),
+            })
+            .collect::<Vec<_>>();
+        format!("{}({})", self.id_of(parent), arguments.join(", "))
+    }
+
+    /// Encodes serialized fields as JSON values, typed after `value_types`.
+    fn json_fields<'a>(
+        &self,
+        value_types: Option<&HashMap<Symbol, ValueType>>,
+        fields: &HashMap<&'a str, String>,
+    ) -> HashMap<&'a str, String> {
+        fields
+            .iter()
+            .map(|(&name, value)| {
+                let value_type = value_types
+                    .and_then(|value_types| value_types.get(&self.interner.get(name)?).copied());
+                (name, json_value(value_type, value).to_string())
+            })
+            .collect()
+    }
+
+    /// Fields written out for the non at `index`: resolved and inherited ones
+    /// when flat, the raw declared ones otherwise. Hidden fields are left out,
+    /// except from the non-flat `non` format which writes the source back.
+    fn serialized_fields(&self, index: NonId) -> Result<HashMap<&str, String>> {
+        let hidden = if self.flat || !matches!(self.format, OutputFormat::Non) {
+            self.hidden_fields(index)
+        } else {
+            HashSet::new()
+        };
+
+        if self.flat {
+            self.fields_at(index)
+                .keys()
+                .filter(|name| !hidden.contains(name))
+                .map(|&name| Ok((self.name(name), self.get_at(index, name)?)))
+                .collect()
+        } else {
+            Ok(self.nons[index]
+                .own_fields()
+                .iter()
+                .filter(|(name, _)| !hidden.contains(name))
+                .map(|(&name, value)| (self.name(name), self.value_to_string(value)))
+                .collect())
         }
-        str
     }
 }
 
 #[derive(Default)]
 pub struct NonDefsBuilder {
-    nons: Option<HashMap<String, Rc<RefCell<Non>>>>,
+    nons: Option<Vec<Non>>,
+    interner: Option<Interner>,
     format: Option<OutputFormat>,
     flat: Option<bool>,
+    ldif: Option<LdifOptions>,
+    csv: Option<CsvOptions>,
+    sandbox: Option<Sandbox>,
+    base_dir: Option<PathBuf>,
 }
 
 impl NonDefsBuilder {
-    pub fn build(self) -> NonDefs {
-        NonDefs::new(self.nons.unwrap(), self.format.unwrap(), self.flat.unwrap())
+    /// Freezes the definitions, failing when a non inherits from itself, as
+    /// resolving it would never end, or passes a template the wrong number of
+    /// arguments.
+    pub fn build(self) -> Result<NonDefs> {
+        let mut non_defs = NonDefs::new(
+            self.nons.unwrap(),
+            self.interner.unwrap(),
+            self.format.unwrap_or(OutputFormat::Non),
+            self.flat.unwrap_or_default(),
+        );
+
+        if let Some(ldif) = self.ldif {
+            non_defs.ldif = ldif;
+        }
+
+        if let Some(csv) = self.csv {
+            non_defs.csv = csv;
+        }
+
+        if let Some(sandbox) = self.sandbox {
+            non_defs.sandbox = sandbox;
+        }
+
+        if let Some(base_dir) = self.base_dir {
+            non_defs.base_dir = base_dir;
+        }
+
+        if let Some(index) =
+            (0..non_defs.nons.len()).find(|&index| non_defs.ancestors(index).contains(&index))
+        {
+            return Err(NonError::InheritanceCycle(
+                non_defs.id_of(index).to_string(),
+            ));
+        }
+
+        for non in &non_defs.nons {
+            for &parent in &non.parents {
+                let params = non_defs.nons[parent].params();
+                match non.arguments(parent) {
+                    Some(arguments) if arguments.len() != params.len() => {
+                        return Err(NonError::TemplateArity {
+                            span: non.span,
+                            template: non_defs.id_of(parent).to_string(),
+                            expected: params.len(),
+                            found: arguments.len(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(non_defs)
     }
 
-    pub fn nons(mut self, nons: HashMap<String, Rc<RefCell<Non>>>) -> Self {
+    pub fn nons(mut self, nons: Vec<Non>) -> Self {
         self.nons = Some(nons);
         self
     }
 
+    pub fn interner(mut self, interner: Interner) -> Self {
+        self.interner = Some(interner);
+        self
+    }
+
     pub fn format(mut self, format: OutputFormat) -> Self {
         self.format = Some(format);
         self
@@ -88,4 +1082,275 @@ impl NonDefsBuilder {
         self.flat = Some(flat);
         self
     }
+
+    pub fn ldif(mut self, ldif: LdifOptions) -> Self {
+        self.ldif = Some(ldif);
+        self
+    }
+
+    pub fn csv(mut self, csv: CsvOptions) -> Self {
+        self.csv = Some(csv);
+        self
+    }
+
+    pub fn sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Sets the directory relative `file()` paths are read from, the working
+    /// directory by default.
+    pub fn base_dir(mut self, base_dir: impl AsRef<Path>) -> Self {
+        self.base_dir = Some(base_dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::NonLexer, parser::NonParser};
+
+    const SOURCE: &str = "univ:
+.name 'Université \"Exemple\"'
+.domain 'exemple.tld'
+
+student:
+.login @
+.mail .login '@' univ.domain
+.size 12
+.quota 'inf'
+!quota type float
+
+alice: student
+.login 'alice'
+";
+
+    fn defs(format: OutputFormat, flat: bool) -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(SOURCE));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(format)
+            .flat(flat)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn flat_toml_round_trips() {
+        let output = defs(OutputFormat::Toml, true).serialize().unwrap();
+        let table = output.parse::<toml::Table>().unwrap();
+
+        assert_eq!(
+            table["univ"]["fields"]["name"].as_str(),
+            Some("Université \"Exemple\"")
+        );
+        let alice = &table["alice"]["fields"];
+        assert_eq!(alice["mail"].as_str(), Some("alice@exemple.tld"));
+        assert_eq!(alice["size"].as_integer(), Some(12));
+        assert_eq!(alice["quota"].as_str(), Some("inf"));
+        assert!(table["alice"].get("parents").is_none());
+    }
+
+    #[test]
+    fn toml_round_trips() {
+        let non_defs = defs(OutputFormat::Toml, false);
+        let table = non_defs
+            .serialize()
+            .unwrap()
+            .parse::<toml::Table>()
+            .unwrap();
+
+        let parents = table["alice"]["parents"].as_array().unwrap();
+        assert_eq!(parents.len(), 1);
+        assert_eq!(parents[0].as_str(), Some("student"));
+
+        let student = non_defs.fields("student").unwrap();
+        let mail = non_defs.value_to_string(&student[&non_defs.interner().get("mail").unwrap()]);
+        assert_eq!(
+            table["student"]["fields"]["mail"].as_str(),
+            Some(mail.as_str())
+        );
+        assert!(table["univ"].get("parents").is_none());
+    }
+
+    #[test]
+    fn extract_parent_needs_distinct_children() {
+        let mut defs = defs(OutputFormat::Non, false);
+
+        assert!(matches!(
+            defs.extract_parent("p", &["alice", "alice"]),
+            Err(NonError::DuplicatedChild(_))
+        ));
+        assert!(matches!(
+            defs.extract_parent("p", &["alice"]),
+            Err(NonError::TooFewChildren)
+        ));
+        assert!(defs.at("p").is_none());
+    }
+
+    #[test]
+    fn file_paths_are_relative_to_the_base_dir() {
+        let mut parser = NonParser::new(NonLexer::new("a:\n.lib file('lib.rs')\n"));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .base_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+            .build()
+            .unwrap();
+
+        let lib = defs.interner().get("lib").unwrap();
+        assert!(defs.get_at(0, lib).unwrap().contains("pub mod nds;"));
+    }
+
+    #[test]
+    fn concrete_leaves_templates_out() {
+        let mut parser = NonParser::new(NonLexer::new(
+            "univ:\n.domain 'exemple.tld'\n\nstudent(u):\n.mail u.domain\n\nalice: univ\n",
+        ));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap();
+
+        assert_eq!(defs.concrete(), vec![defs.index_of("alice").unwrap()]);
+    }
+
+    #[test]
+    fn flat_exports_type_computed_values() {
+        let mut parser = NonParser::new(NonLexer::new(
+            "base:\n.base_quota 15\n\nuser: base\n.quota .base_quota * 2 + 1\n.neg -.base_quota\n.ratio .quota / 2.0\n.text .base_quota ' GB'\n",
+        ));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Json)
+            .flat(true)
+            .build()
+            .unwrap();
+
+        let nons: serde_json::Value = serde_json::from_str(&defs.serialize().unwrap()).unwrap();
+        let user = &nons[1]["fields"];
+        assert_eq!(user["base_quota"], serde_json::json!(15));
+        assert_eq!(user["quota"], serde_json::json!(31));
+        assert_eq!(user["neg"], serde_json::json!(-15));
+        assert_eq!(user["ratio"], serde_json::json!(15.5));
+        assert_eq!(user["text"], serde_json::json!("15 GB"));
+    }
+
+    #[test]
+    fn reference_cycles_are_errors() {
+        let mut parser =
+            NonParser::new(NonLexer::new("a:\n.x .y\n.y join(.x, ',')\n\nb:\n.z a.x\n"));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap();
+
+        match defs.get("a", "x") {
+            Err(NonError::ReferenceCycle(cycle)) => assert_eq!(cycle, "a.x -> a.y -> a.x"),
+            result => panic!("Expected a reference cycle, found {:?}", result),
+        }
+        assert!(matches!(
+            defs.get("b", "z"),
+            Err(NonError::ReferenceCycle(_))
+        ));
+    }
+
+    const MODIFIERS: &str = "student:
+.login @
+.mail .login '@exemple.tld'
+.quota 10
+!login hidden
+
+alice: student
+-quota
+
+bob: alice
+.quota 20
+";
+
+    fn modifiers(format: OutputFormat, flat: bool) -> NonDefs {
+        let mut parser = NonParser::new(NonLexer::new(MODIFIERS));
+        parser.parse().unwrap();
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(format)
+            .flat(flat)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn deleted_fields_are_not_inherited() {
+        let defs = modifiers(OutputFormat::Json, true);
+
+        assert!(matches!(
+            defs.get("alice", "quota"),
+            Err(NonError::UnknownField { field, .. }) if field == "quota"
+        ));
+        assert_eq!(defs.get("bob", "quota").unwrap(), "20");
+        assert_eq!(defs.get("bob", "mail").unwrap(), "bob@exemple.tld");
+    }
+
+    #[test]
+    fn hidden_fields_are_left_out_of_exports() {
+        let json = modifiers(OutputFormat::Json, true).serialize().unwrap();
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        let alice = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|non| non["id"] == "alice")
+            .unwrap();
+        assert_eq!(
+            alice["fields"],
+            serde_json::json!({ "mail": "alice@exemple.tld" })
+        );
+
+        let yaml = modifiers(OutputFormat::Yaml, true).serialize().unwrap();
+        assert!(yaml.contains("bob@exemple.tld"));
+        assert!(!yaml.contains("login"));
+
+        let non = modifiers(OutputFormat::Non, true).serialize().unwrap();
+        assert!(non.contains(".mail 'alice@exemple.tld'"));
+        assert!(!non.contains(".login"));
+    }
+
+    #[test]
+    fn modifiers_are_written_back() {
+        let non = modifiers(OutputFormat::Non, false).serialize().unwrap();
+
+        assert!(non.contains("!login hidden"));
+        assert!(non.contains("-quota"));
+        assert!(non.contains(".login @"));
+
+        let mut parser = NonParser::new(NonLexer::new(&non));
+        parser.parse().unwrap();
+        let reparsed = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .format(OutputFormat::Json)
+            .flat(true)
+            .build()
+            .unwrap();
+        assert!(reparsed.get("alice", "quota").is_err());
+        assert!(
+            reparsed
+                .hidden_fields(reparsed.index_of("bob").unwrap())
+                .len()
+                == 1
+        );
+    }
 }
@@ -1,163 +1,203 @@
-use std::{
-    cell::{Ref, RefCell},
-    collections::{HashMap, HashSet},
-    rc::Rc,
-};
+use std::collections::{HashMap, HashSet};
 
 use serde::Serialize;
 
-#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+use crate::{
+    error::{NonError, Result},
+    function::Function,
+    interner::{Interner, Symbol},
+    operator::Operator,
+    schema::{FieldSchema, ValueType},
+    token::Span,
+};
+
+/// Index of a non inside the arena owned by [`NonDefs`](crate::nds::NonDefs).
+pub type NonId = usize;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct Non {
-    id: String,
-    fields: HashMap<String, FieldValue>,
-    pub parents: Vec<Rc<RefCell<Non>>>,
+    id: Symbol,
+    fields: HashMap<Symbol, FieldValue>,
+    field_spans: HashMap<Symbol, Span>,
+    schema: HashMap<Symbol, FieldSchema>,
+    unset: HashSet<Symbol>,
+    params: Vec<Symbol>,
+    arguments: HashMap<NonId, Vec<Argument>>,
+    pub parents: Vec<NonId>,
+    pub span: Span,
+}
+
+/// Value given to a template parameter, as in `alice: student(univ)`.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub enum Argument {
+    Non(NonId),
+
+    /// Parameter of the inheriting template itself, forwarded by position.
+    Param(usize),
 }
 
 impl Non {
-    pub fn new(
-        id: String,
-        fields: HashMap<String, FieldValue>,
-        parents: Vec<Rc<RefCell<Non>>>,
-    ) -> Self {
+    pub fn new(id: Symbol, fields: HashMap<Symbol, FieldValue>, parents: Vec<NonId>) -> Self {
         Self {
             id,
             fields,
+            field_spans: HashMap::new(),
+            schema: HashMap::new(),
+            unset: HashSet::new(),
+            params: Vec::new(),
+            arguments: HashMap::new(),
             parents,
+            span: Span::default(),
         }
     }
 
-    pub fn from_id(id: String) -> Self {
-        let fields = HashMap::new();
-        Non {
-            id,
-            fields,
-            ..Default::default()
-        }
+    pub fn from_id(id: Symbol) -> Self {
+        Non::new(id, HashMap::new(), Vec::new())
     }
 
-    pub fn id(&self) -> String {
-        self.id.clone()
+    pub fn id(&self) -> Symbol {
+        self.id
     }
 
-    pub fn get(&self, field_name: &str) -> Option<String> {
-        self.fields()
-            .get(field_name)
-            .map(|field| self.resolve_field(field.clone()))
+    pub fn own_fields(&self) -> &HashMap<Symbol, FieldValue> {
+        &self.fields
     }
 
-    pub fn add_field(&mut self, name: String, value: FieldValue) {
+    pub fn add_field(&mut self, name: Symbol, value: FieldValue) {
         self.fields.insert(name, value);
     }
 
-    pub fn resolve_field(&self, field_value: FieldValue) -> String {
-        let mut str = String::new();
-        match field_value {
-            FieldValue::Litteral(v) => str.push_str(&v),
-            FieldValue::Vec(field_values) => {
-                for field_value in field_values {
-                    str.push_str(self.resolve_field(field_value).as_str());
-                }
-            }
-            FieldValue::FieldReference(reference) => {
-                if reference == "id" {
-                    str.push_str(&self.id());
-                } else {
-                    str.push_str(self.get(&reference).unwrap().as_str())
-                }
-            }
-            FieldValue::ObjRef(non, field_name) => {
-                str.push_str(non.borrow().get(&field_name).unwrap().as_str())
-            }
-        }
-        str
+    /// Adds a field, remembering where it was declared in the source.
+    pub fn add_field_at(&mut self, name: Symbol, value: FieldValue, span: Span) {
+        self.add_field(name, value);
+        self.field_spans.insert(name, span);
     }
 
-    pub fn union(&self, other: Ref<'_, Non>) -> Result<Non, String> {
-        let fields = self.fields();
-        let other_fields = other.fields();
+    pub fn remove_field(&mut self, name: Symbol) -> Option<FieldValue> {
+        self.field_spans.remove(&name);
+        self.fields.remove(&name)
+    }
 
-        for (name, value) in &fields {
-            if let Some(other_value) = other_fields.get(name) {
-                if other_value != value {
-                    return Err(format!("Duplicated field '{}' without same value.", name));
-                }
-            }
-        }
+    /// Removes the field and hides the one inherited under the same name, as
+    /// a `-field` line does.
+    pub fn unset_field(&mut self, name: Symbol) {
+        self.remove_field(name);
+        self.unset.insert(name);
+    }
 
-        for (name, value) in &other_fields {
-            if let Some(other_value) = fields.get(name) {
-                if *other_value != *value {
-                    return Err(format!("Duplicated field '{}' without same value.", name));
-                }
-            }
-        }
+    /// Inherited fields hidden by [`Non::unset_field`].
+    pub fn unset_fields(&self) -> &HashSet<Symbol> {
+        &self.unset
+    }
+
+    pub fn field_span(&self, name: Symbol) -> Option<Span> {
+        self.field_spans.get(&name).copied()
+    }
+
+    pub fn schema(&self) -> &HashMap<Symbol, FieldSchema> {
+        &self.schema
+    }
+
+    pub fn add_schema(&mut self, name: Symbol, schema: FieldSchema) {
+        self.schema
+            .entry(name)
+            .and_modify(|existing| existing.merge(&schema))
+            .or_insert(schema);
+    }
 
-        let mut union_fields = self.fields();
-        union_fields.extend(other_fields);
+    /// Parameters of the non when it is a template, as in `student(u):`.
+    pub fn params(&self) -> &[Symbol] {
+        &self.params
+    }
 
-        let mut parents = self.parents.iter().cloned().collect::<Vec<_>>();
-        parents.extend(other.parents.iter().cloned());
+    pub fn set_params(&mut self, params: Vec<Symbol>) {
+        self.params = params;
+    }
 
-        // filter parents to avoid duplications
-        let mut seen = HashSet::new();
-        parents.retain(|p| {
-            let ptr = Rc::as_ptr(p);
-            seen.insert(ptr)
-        });
+    /// Arguments given to the template `parent`, if any.
+    pub fn arguments(&self, parent: NonId) -> Option<&[Argument]> {
+        self.arguments.get(&parent).map(Vec::as_slice)
+    }
 
-        Ok(Non::new(self.id(), union_fields, parents))
+    pub fn set_arguments(&mut self, parent: NonId, arguments: Vec<Argument>) {
+        self.arguments.insert(parent, arguments);
     }
 
-    pub fn serialize_non(&self, flat: bool) -> String {
+    pub fn serialize_non(
+        &self,
+        interner: &Interner,
+        parents: &[&str],
+        fields: &HashMap<&str, String>,
+        flat: bool,
+    ) -> String {
         let mut str = String::new();
 
-        str.push_str(&self.id().to_string());
+        str.push_str(interner.resolve(self.id));
+
+        if !flat && !self.params.is_empty() {
+            let params = self
+                .params
+                .iter()
+                .map(|&param| interner.resolve(param))
+                .collect::<Vec<_>>();
+            str.push_str(&format!("({})", params.join(", ")));
+        }
+
         str.push(':');
 
         if !flat {
-            for parent_ref in &self.parents {
-                let parent = parent_ref.borrow();
+            for parent in parents {
                 str.push(' ');
-                str.push_str(&parent.id());
+                str.push_str(parent);
             }
         }
 
         str.push('\n');
 
-        let fields = if flat { &self.fields() } else { &self.fields };
-
         for (key, value) in fields {
-            let serialized_field_value = if flat {
-                self.resolve_field(value.clone())
-            } else {
-                value.to_string()
-            };
-            str.push_str(&format!(".{} {}\n", key, serialized_field_value));
+            str.push_str(&format!(".{} {}\n", key, value));
+        }
+
+        if !flat {
+            for name in &self.unset {
+                if !self.fields.contains_key(name) {
+                    str.push_str(&format!("-{}\n", interner.resolve(*name)));
+                }
+            }
+
+            for (name, field_schema) in &self.schema {
+                str.push_str(&format!("!{}{}\n", interner.resolve(*name), field_schema));
+            }
         }
 
         str
     }
 
-    pub fn serialize_json(&self, flat: bool) -> String {
+    /// Writes the non as a JSON object. Field values must already be JSON
+    /// encoded.
+    pub fn serialize_json(
+        &self,
+        interner: &Interner,
+        parents: &[&str],
+        fields: &HashMap<&str, String>,
+        flat: bool,
+    ) -> String {
         let mut str = String::new();
 
         str.push_str("{\n");
         str.push_str("\t\"id\": \"");
-        str.push_str(&self.id());
-        str.push_str("\"");
+        str.push_str(interner.resolve(self.id));
+        str.push('"');
 
-        let fields = if flat { &self.fields() } else { &self.fields };
-
-        if !(fields.is_empty() && self.parents.is_empty()) {
+        if !(fields.is_empty() && parents.is_empty()) {
             str.push(',');
         }
 
-        if !flat && !self.parents.is_empty() {
+        if !flat && !parents.is_empty() {
             str.push_str("\n\t\"parents\": [\n");
-            let parent_str = self
-                .parents
+            let parent_str = parents
                 .iter()
-                .map(|parent| format!("\t\t\"{}\"", parent.borrow().id()))
+                .map(|parent| format!("\t\t\"{}\"", parent))
                 .collect::<Vec<String>>()
                 .join(",\n");
             str.push_str(&parent_str);
@@ -172,17 +212,7 @@ impl Non {
 
             let fields_str = fields
                 .iter()
-                .map(|(field_name, value)| {
-                    if flat {
-                        format!(
-                            "\t\t\"{}\": \"{}\"",
-                            field_name,
-                            self.resolve_field(value.clone())
-                        )
-                    } else {
-                        format!("\t\t\"{}\": \"{}\"", field_name, value.to_string())
-                    }
-                })
+                .map(|(field_name, value)| format!("\t\t\"{}\": {}", field_name, value))
                 .collect::<Vec<_>>()
                 .join(",\n");
             str.push_str(&fields_str);
@@ -193,55 +223,101 @@ impl Non {
         str
     }
 
-    pub fn serialize_yaml(&self, flat: bool) -> String {
+    /// Writes the non as a YAML mapping entry. Field values must already be
+    /// YAML encoded.
+    pub fn serialize_yaml(
+        &self,
+        interner: &Interner,
+        parents: &[&str],
+        fields: &HashMap<&str, String>,
+        flat: bool,
+    ) -> String {
         let mut str = String::new();
 
-        str.push_str(&self.id());
-        str.push_str(":");
+        str.push_str(interner.resolve(self.id));
+        str.push(':');
 
-        if !self.parents.is_empty() {
-            str.push_str("\n\tparents:\n\t");
-            let parent_str = self
-                .parents
-                .iter()
-                .map(|parent| format!("  - {}", parent.borrow().id()))
-                .collect::<Vec<String>>()
-                .join("\n\t");
-            str.push_str(&parent_str);
+        if !flat && !parents.is_empty() {
+            str.push_str("\n  parents:");
+            for parent in parents {
+                str.push_str(&format!("\n    - {}", parent));
+            }
         }
 
-        if !self.fields.is_empty() {
-            str.push('\n');
+        for (field_name, value) in fields {
+            str.push_str(&format!("\n  {}: {}", field_name, value));
         }
 
-        let fields = if flat { &self.fields() } else { &self.fields };
+        str.push('\n');
+        str
+    }
 
-        let fields_str = fields
-            .iter()
-            .map(|(field_name, value)| {
-                if flat {
-                    format!("\t{}: {}", field_name, self.resolve_field(value.clone()))
-                } else {
-                    format!("\t{}: {}", field_name, value.to_string())
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
-        str.push_str(&fields_str);
+    /// Writes the non as a TOML table, with its fields in a `fields`
+    /// sub-table. Field values must already be TOML encoded.
+    pub fn serialize_toml(
+        &self,
+        interner: &Interner,
+        parents: &[&str],
+        fields: &HashMap<&str, String>,
+        flat: bool,
+    ) -> String {
+        let mut str = String::new();
+        let id = toml_key(interner.resolve(self.id));
+
+        str.push_str(&format!("[{}]\n", id));
+
+        if !flat && !parents.is_empty() {
+            let parent_str = parents
+                .iter()
+                .map(|parent| format!("\"{}\"", parent))
+                .collect::<Vec<_>>()
+                .join(", ");
+            str.push_str(&format!("parents = [{}]\n", parent_str));
+        }
+
+        if !fields.is_empty() {
+            str.push_str(&format!("\n[{}.fields]\n", id));
+
+            for (field_name, value) in fields {
+                str.push_str(&format!("{} = {}\n", toml_key(field_name), value));
+            }
+        }
 
-        str.push_str("\n");
         str
     }
+}
+
+/// Writes `value` as a `.non` litteral, failing when [`is_litteral`] does
+/// not hold.
+pub fn litteral(value: &str) -> Result<String> {
+    if is_litteral(value) {
+        Ok(format!("'{}'", value))
+    } else {
+        Err(NonError::InvalidLitteral(value.to_string()))
+    }
+}
 
-    fn fields(&self) -> HashMap<String, FieldValue> {
-        let mut map = HashMap::new();
+/// Returns whether `value` can be written as a `.non` litteral, which has no
+/// escape sequences and cannot be empty.
+pub fn is_litteral(value: &str) -> bool {
+    !value.is_empty() && !value.contains('\'')
+}
 
-        for parent_ref in &self.parents {
-            map.extend(parent_ref.borrow().fields().into_iter());
-        }
+/// Returns whether `value` is a valid non id or field name.
+pub fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
 
-        map.extend(self.fields.clone().into_iter());
-        map
+/// Quotes `key` unless it is a valid TOML bare key.
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        format!("\"{}\"", key)
     }
 }
 
@@ -249,25 +325,135 @@ impl Non {
 pub enum FieldValue {
     Litteral(String),
     Vec(Vec<FieldValue>),
-    FieldReference(String),
-    ObjRef(Rc<RefCell<Non>>, String),
+    FieldReference(Symbol),
+    ObjRef(NonId, Symbol),
+
+    /// `"text {.ref} text"`, concatenating litteral text and the values of
+    /// its placeholders.
+    Interpolated(Vec<FieldValue>),
+
+    /// A number, kept as written.
+    Number(String),
+
+    /// `true` or `false`.
+    Bool(bool),
+
+    /// `left operator right`, evaluated when resolved.
+    Binary(Operator, Box<FieldValue>, Box<FieldValue>),
+
+    /// `-operand`.
+    Neg(Box<FieldValue>),
+
+    /// `param.field` inside a template, the non bound to parameter `usize` of
+    /// the template `NonId` being looked up when resolved.
+    ParamRef(NonId, usize, Symbol),
+
+    /// `is non`, whether the resolved non is `non` or one of its descendants.
+    Is(NonId),
+
+    /// `if condition then value else value`, only the taken branch being
+    /// resolved.
+    If(Box<FieldValue>, Box<FieldValue>, Box<FieldValue>),
+
+    /// `function(arg, ...)`, each argument being a value of its own.
+    Call(Function, Vec<FieldValue>),
 }
 
 impl FieldValue {
-    pub fn to_string(&self) -> String {
+    /// Type every resolution of the value has, when it can be told without
+    /// resolving it: numbers and arithmetic on them, comparisons and tests.
+    /// `reference` gives the type of the fields the value refers to. Other
+    /// values resolve to any text.
+    pub fn value_type(
+        &self,
+        reference: &mut dyn FnMut(&FieldValue) -> Option<ValueType>,
+    ) -> Option<ValueType> {
         match self {
-            FieldValue::Litteral(str) => str.clone(),
-            FieldValue::Vec(field_values) => field_values
-                .iter()
-                .map(|f| f.to_string())
-                .collect::<Vec<_>>()
-                .join(" "),
-            FieldValue::FieldReference(reference) => {
-                if reference == "id" { "@" } else { reference }.to_string()
+            Self::Number(number) if number.contains('.') => Some(ValueType::Float),
+            Self::Number(_) => Some(ValueType::Int),
+            Self::Bool(_) => Some(ValueType::Bool),
+            Self::Neg(operand) => numeric(operand.value_type(reference)),
+            Self::Binary(operator, _, _) if operator.is_comparison() => Some(ValueType::Bool),
+            // integers only give integers, as division truncates
+            Self::Binary(_, left, right) => {
+                match (
+                    numeric(left.value_type(reference))?,
+                    numeric(right.value_type(reference))?,
+                ) {
+                    (ValueType::Int, ValueType::Int) => Some(ValueType::Int),
+                    _ => Some(ValueType::Float),
+                }
             }
-            FieldValue::ObjRef(reference, field) => {
-                format!("{}.{}", reference.borrow().id(), field.clone())
+            Self::Is(_) => Some(ValueType::Bool),
+            Self::If(_, then, otherwise) => {
+                let then = then.value_type(reference);
+                then.filter(|&then| otherwise.value_type(reference) == Some(then))
             }
+            Self::FieldReference(_) | Self::ObjRef(..) | Self::ParamRef(..) => reference(self),
+            Self::Litteral(_) | Self::Vec(_) | Self::Interpolated(_) | Self::Call(..) => None,
         }
     }
 }
+
+fn numeric(value_type: Option<ValueType>) -> Option<ValueType> {
+    value_type.filter(|value_type| matches!(value_type, ValueType::Int | ValueType::Float))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn litteral_rejects_quotes_and_empty_values() {
+        assert_eq!(litteral("exemple.tld").unwrap(), "'exemple.tld'");
+        assert!(litteral("it's").is_err());
+        assert!(litteral("").is_err());
+    }
+
+    #[test]
+    fn value_types_are_told_from_values() {
+        let number = |number: &str| Box::new(FieldValue::Number(number.to_string()));
+        let reference = Box::new(FieldValue::FieldReference(Symbol::ID));
+        let value_type = |value: FieldValue| value.value_type(&mut |_| Some(ValueType::Int));
+
+        assert_eq!(value_type(*number("21")), Some(ValueType::Int));
+        assert_eq!(
+            value_type(FieldValue::Neg(number("1.5"))),
+            Some(ValueType::Float)
+        );
+        assert_eq!(
+            value_type(FieldValue::Binary(Operator::Lt, number("1"), number("2"))),
+            Some(ValueType::Bool)
+        );
+        assert_eq!(
+            value_type(FieldValue::Binary(
+                Operator::Add,
+                Box::new(FieldValue::Binary(
+                    Operator::Mul,
+                    reference.clone(),
+                    number("2")
+                )),
+                number("1")
+            )),
+            Some(ValueType::Int)
+        );
+        assert_eq!(
+            value_type(FieldValue::Binary(
+                Operator::Div,
+                reference.clone(),
+                number("2.0")
+            )),
+            Some(ValueType::Float)
+        );
+        assert_eq!(value_type(FieldValue::Neg(reference)), Some(ValueType::Int));
+        assert_eq!(
+            value_type(FieldValue::Binary(
+                Operator::Add,
+                Box::new(FieldValue::Litteral("2".to_string())),
+                number("1")
+            )),
+            None
+        );
+        assert_eq!(value_type(FieldValue::Litteral("21".to_string())), None);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use serde::Serialize;
+
+use crate::error::{NonError, Result};
+
+/// Binary operator of a field expression, such as `.base_quota * 2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "+" => Some(Self::Add),
+            "-" => Some(Self::Sub),
+            "*" => Some(Self::Mul),
+            "/" => Some(Self::Div),
+            "%" => Some(Self::Rem),
+            "==" => Some(Self::Eq),
+            "!=" => Some(Self::Ne),
+            "<" => Some(Self::Lt),
+            "<=" => Some(Self::Le),
+            ">" => Some(Self::Gt),
+            ">=" => Some(Self::Ge),
+            _ => None,
+        }
+    }
+
+    /// Binding strength, operators of higher precedence are applied first.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge => 1,
+            Self::Add | Self::Sub => 2,
+            Self::Mul | Self::Div | Self::Rem => 3,
+        }
+    }
+
+    /// Whether the operator compares its operands, giving `true` or `false`.
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == 1
+    }
+
+    /// Applies the operator to resolved operands.
+    ///
+    /// Arithmetic stays on integers when both operands are integers, dividing
+    /// with truncation, and switches to floats otherwise. Comparisons give
+    /// `true` or `false`; only `==` and `!=` accept operands that are not
+    /// numbers, comparing them as text.
+    pub fn apply(&self, left: &str, right: &str) -> Result<String> {
+        if matches!(self, Self::Eq | Self::Ne) {
+            let equal = match (parse_number(left), parse_number(right)) {
+                (Some(left), Some(right)) => left.compare(right) == Some(Ordering::Equal),
+                _ => left == right,
+            };
+            return Ok((equal == (*self == Self::Eq)).to_string());
+        }
+
+        let left = self.operand(left)?;
+        let right = self.operand(right)?;
+
+        let result = match (self, left, right) {
+            (Self::Lt | Self::Le | Self::Gt | Self::Ge, left, right) => {
+                let ordering = left.compare(right);
+                let result = match self {
+                    Self::Lt => ordering == Some(Ordering::Less),
+                    Self::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    Self::Gt => ordering == Some(Ordering::Greater),
+                    _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                };
+                return Ok(result.to_string());
+            }
+            (Self::Div | Self::Rem, _, right) if right.is_zero() => {
+                return Err(NonError::DivisionByZero);
+            }
+            (_, Number::Int(left), Number::Int(right)) => {
+                let result = match self {
+                    Self::Add => left.checked_add(right),
+                    Self::Sub => left.checked_sub(right),
+                    Self::Mul => left.checked_mul(right),
+                    Self::Div => left.checked_div(right),
+                    _ => left.checked_rem(right),
+                };
+                Number::Int(result.ok_or_else(|| NonError::Overflow(self.to_string()))?)
+            }
+            (_, left, right) => {
+                let (left, right) = (left.as_float(), right.as_float());
+                Number::Float(match self {
+                    Self::Add => left + right,
+                    Self::Sub => left - right,
+                    Self::Mul => left * right,
+                    Self::Div => left / right,
+                    _ => left % right,
+                })
+            }
+        };
+
+        Ok(result.to_string())
+    }
+
+    fn operand(&self, value: &str) -> Result<Number> {
+        parse_number(value).ok_or_else(|| NonError::NotANumber {
+            operator: self.to_string(),
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => f.write_str("+"),
+            Self::Sub => f.write_str("-"),
+            Self::Mul => f.write_str("*"),
+            Self::Div => f.write_str("/"),
+            Self::Rem => f.write_str("%"),
+            Self::Eq => f.write_str("=="),
+            Self::Ne => f.write_str("!="),
+            Self::Lt => f.write_str("<"),
+            Self::Le => f.write_str("<="),
+            Self::Gt => f.write_str(">"),
+            Self::Ge => f.write_str(">="),
+        }
+    }
+}
+
+/// Negates a resolved operand of unary `-`.
+pub fn negate(value: &str) -> Result<String> {
+    let negated = match parse_number(value) {
+        Some(Number::Int(value)) => Number::Int(
+            value
+                .checked_neg()
+                .ok_or(NonError::Overflow("-".to_string()))?,
+        ),
+        Some(Number::Float(value)) => Number::Float(-value),
+        None => {
+            return Err(NonError::NotANumber {
+                operator: "-".to_string(),
+                value: value.to_string(),
+            });
+        }
+    };
+
+    Ok(negated.to_string())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Self::Int(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self.as_float() == 0.0
+    }
+
+    fn compare(self, other: Number) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(left), Self::Int(right)) => Some(left.cmp(&right)),
+            (left, right) => left.as_float().partial_cmp(&right.as_float()),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Float(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Option<Number> {
+    if let Ok(value) = value.parse() {
+        return Some(Number::Int(value));
+    }
+
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(Number::Float)
+}
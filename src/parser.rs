@@ -1,162 +1,692 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
 use crate::{
+    error::{NonError, Result},
+    function::Function,
+    interner::{Interner, Symbol},
     lexer::NonLexer,
-    non::{FieldValue, Non},
-    token::{Token, TokenKind},
+    non::{Argument, FieldValue, Non, NonId},
+    operator::Operator,
+    schema::{FieldSchema, ValueType},
+    token::{Span, Token, TokenKind},
 };
 
 #[derive(Debug)]
 pub struct NonParser<'a> {
-    current_token: Token,
+    current_token: Token<'a>,
     lexer: NonLexer<'a>,
-    missing: HashMap<String, Rc<RefCell<Non>>>,
-    pub nons: HashMap<String, Rc<RefCell<Non>>>,
+    ids: HashMap<Symbol, NonId>,
+    missing: HashMap<Symbol, NonId>,
+    /// Non whose fields are being parsed, to look its parameters up.
+    current: Option<NonId>,
+    pub nons: Vec<Non>,
+    pub interner: Interner,
 }
 
 impl<'a> NonParser<'a> {
     pub fn new(lexer: NonLexer<'a>) -> Self {
         Self {
             current_token: Token::default(),
-            nons: HashMap::new(),
+            nons: Vec::new(),
+            ids: HashMap::new(),
             missing: HashMap::new(),
+            current: None,
+            interner: Interner::new(),
             lexer,
         }
     }
 
-    pub fn parse(&mut self) {
+    pub fn parse(&mut self) -> Result<()> {
         self.advance();
         self.skip_newlines();
 
-        while self.is_kind(TokenKind::Identifier) {
-            self.parse_non();
+        while self.is_name() {
+            self.parse_non()?;
         }
 
-        if !self.missing.is_empty() {
-            println!("Missing non in file:");
-            for id in self.missing.keys() {
-                println!("{}", id);
+        self.check_missing()
+    }
+
+    /// Fails on the first id referred to but never declared.
+    fn check_missing(&self) -> Result<()> {
+        match self.missing.iter().min_by_key(|&(_, &non)| non) {
+            Some((&id, _)) => Err(NonError::UnknownNon(self.interner.resolve(id).to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses `lexer` on top of the nons parsed so far: declarations of an
+    /// existing id add or override its fields and append parents, and `-field`
+    /// lines delete a field. Declaring or referring to an id unknown so far is
+    /// an error unless `allow_new`.
+    pub fn parse_overlay(&mut self, lexer: NonLexer<'a>, allow_new: bool) -> Result<()> {
+        let known = self.nons.len();
+        self.lexer = lexer;
+        self.advance();
+        self.skip_newlines();
+
+        while self.is_name() {
+            let non = self.parse_non()?;
+            if non >= known && !allow_new {
+                let id = self.interner.resolve(self.nons[non].id());
+                return Err(NonError::UnknownNon(id.to_string()));
             }
-            panic!();
         }
+
+        self.check_missing()
+    }
+
+    /// Sets the field `field` of `id` to `value`, parsed like the value of a
+    /// field declaration. The non and the field must already exist, be it
+    /// through inheritance, unless `allow_new`. `value` must hold a single
+    /// line value and nothing else.
+    pub fn parse_assignment(
+        &mut self,
+        id: &str,
+        field: &str,
+        value: &'a str,
+        allow_new: bool,
+    ) -> Result<()> {
+        let non = match self.interner.get(id).and_then(|id| self.ids.get(&id)) {
+            Some(&non) => non,
+            None if allow_new => {
+                let id = self.interner.intern(id);
+                let non = self.find_nom_by_id_or_create(id);
+                self.missing.remove(&id);
+                non
+            }
+            None => return Err(NonError::UnknownNon(id.to_string())),
+        };
+        self.current = Some(non);
+
+        let known = self
+            .interner
+            .get(field)
+            .is_some_and(|field| self.has_field(non, field));
+        if !known && !allow_new {
+            return Err(NonError::UnknownField {
+                non: id.to_string(),
+                field: field.to_string(),
+            });
+        }
+
+        if value.trim().is_empty() {
+            return Err(NonError::InvalidFieldValue(value.to_string()));
+        }
+
+        let source = value;
+        self.lexer = NonLexer::new(value);
+        self.advance();
+        let field = self.interner.intern(field);
+        let value = self.parse_values()?;
+
+        // a token the lexer fails on reads as the end of the value
+        self.eat(TokenKind::Space);
+        if !self.is_kind(TokenKind::Eof) || !self.lexer.is_empty() {
+            return Err(NonError::InvalidFieldValue(source.to_string()));
+        }
+        self.nons[non].add_field(field, value);
+
+        self.check_missing()
     }
 
-    fn parse_non(&mut self) {
-        let id = self.current_token.get_token_str_raw_value().unwrap();
-        let non = self
-            .missing
-            .remove(&id)
-            .unwrap_or(Rc::new(RefCell::new(Non::from_id(id.clone()))));
+    /// Returns whether the non declares or inherits `field`.
+    fn has_field(&self, non: NonId, field: Symbol) -> bool {
+        let non = &self.nons[non];
+        non.own_fields().contains_key(&field)
+            || !non.unset_fields().contains(&field)
+                && non
+                    .parents
+                    .iter()
+                    .any(|&parent| self.has_field(parent, field))
+    }
+
+    fn parse_non(&mut self) -> Result<NonId> {
+        let id = self.intern_current();
+        let non = self.find_nom_by_id_or_create(id);
+        self.missing.remove(&id);
+        self.nons[non].span = self.current_token.span;
+        self.current = Some(non);
 
         self.advance();
 
+        if self.is_kind(TokenKind::LParen) {
+            let params = self.parse_identifier_list();
+            self.nons[non].set_params(params);
+        }
+
         if !self.eat(TokenKind::Colon) {
             panic!("Colon required after non declaration.");
         }
 
-        // self.skip_spaces();
-
-        while self.eat(TokenKind::Space) && self.is_kind(TokenKind::Identifier) {
-            let parent_name = self.current_token.get_token_str_raw_value().unwrap();
+        while self.eat(TokenKind::Space) && self.is_name() {
+            let parent_name = self.intern_current();
             let parent = self.find_nom_by_id_or_create(parent_name);
-            non.borrow_mut().parents.push(parent);
+            if !self.nons[non].parents.contains(&parent) {
+                self.nons[non].parents.push(parent);
+            }
             self.advance();
+
+            if self.is_kind(TokenKind::LParen) {
+                let arguments = self
+                    .parse_identifier_list()
+                    .into_iter()
+                    .map(|name| match self.param_position(name) {
+                        Some(position) => Argument::Param(position),
+                        None => Argument::Non(self.find_nom_by_id_or_create(name)),
+                    })
+                    .collect();
+                self.nons[non].set_arguments(parent, arguments);
+            }
         }
 
         if !self.eat(TokenKind::Newline) {
             panic!("Newline required.");
         }
 
-        while self.eat(TokenKind::Dot) {
-            let (field_name, field_value) = self.parse_field();
-            non.borrow_mut().add_field(field_name, field_value);
-        }
+        loop {
+            let span = self.current_token.span;
 
-        let id = non.borrow().id();
-        self.nons.insert(id, non);
+            if self.eat(TokenKind::Dot) {
+                let (field_name, field_value) = self.parse_field()?;
+                self.nons[non].add_field_at(field_name, field_value, span);
+            } else if self.eat(TokenKind::Bang) {
+                let (field_name, mut field_schema) = self.parse_schema();
+                field_schema.span = span;
+                self.nons[non].add_schema(field_name, field_schema);
+            } else if self.eat(TokenKind::Minus) {
+                let field_name = self.parse_deletion();
+                self.nons[non].unset_field(field_name);
+            } else {
+                break;
+            }
+        }
 
         self.skip_newlines();
+        Ok(non)
     }
 
-    fn find_nom_by_id_or_create(&mut self, id: String) -> Rc<RefCell<Non>> {
-        if self.nons.contains_key(&id) {
-            self.nons.get(&id).cloned().unwrap()
-        } else if self.missing.contains_key(&id) {
-            self.missing.get(&id).cloned().unwrap()
+    fn find_nom_by_id_or_create(&mut self, id: Symbol) -> NonId {
+        if let Some(&non) = self.ids.get(&id) {
+            non
         } else {
-            let non = Rc::new(RefCell::new(Non::from_id(id.clone())));
-            self.missing.insert(id, non.clone());
+            let non = self.nons.len();
+            self.nons.push(Non::from_id(id));
+            self.ids.insert(id, non);
+            self.missing.insert(id, non);
             non
         }
     }
 
-    fn parse_field(&mut self) -> (String, FieldValue) {
-        let field_name = if self.is_kind(TokenKind::Identifier) {
-            self.current_token.get_token_str_raw_value().unwrap()
+    /// Parses `(name, ...)` from the opening parenthesis, moving past the
+    /// closing one.
+    fn parse_identifier_list(&mut self) -> Vec<Symbol> {
+        let mut names = Vec::new();
+        self.advance();
+
+        loop {
+            self.eat(TokenKind::Space);
+            if !self.is_name() {
+                panic!(
+                    "{}: Identifier required in parenthesis.",
+                    self.current_token.span
+                );
+            }
+            names.push(self.intern_current());
+            self.advance();
+            self.eat(TokenKind::Space);
+
+            if self.eat(TokenKind::RParen) {
+                return names;
+            } else if !self.eat(TokenKind::Comma) {
+                panic!(
+                    "{}: Comma or closing parenthesis required.",
+                    self.current_token.span
+                );
+            }
+        }
+    }
+
+    /// Position of `name` among the parameters of the non being parsed.
+    fn param_position(&self, name: Symbol) -> Option<usize> {
+        let non = self.current?;
+        self.nons[non]
+            .params()
+            .iter()
+            .position(|&param| param == name)
+    }
+
+    /// Interns the value of the current identifier, number or litteral token.
+    fn intern_current(&mut self) -> Symbol {
+        let value = self.current_token.value.unwrap();
+        self.interner.intern(value)
+    }
+
+    fn parse_field(&mut self) -> Result<(Symbol, FieldValue)> {
+        let field_name = if self.is_name() {
+            self.intern_current()
         } else {
             panic!("Field name must be an identifier.");
         };
 
-        let mut value_vec = Vec::new();
         self.advance();
-        while !(self.eat(TokenKind::Newline) || self.eat(TokenKind::Eof)) {
-            if self.eat(TokenKind::Space) {
-                let value = match self.current_token.kind {
-                    TokenKind::Dot => {
-                        self.advance();
-                        if self.is_kind(TokenKind::Identifier) {
-                            FieldValue::FieldReference(
-                                self.current_token.get_token_str_raw_value().unwrap(),
-                            )
-                        } else {
-                            panic!("Token must be an identifier.");
-                        }
-                    }
+        Ok((field_name, self.parse_field_value()?))
+    }
 
-                    TokenKind::Identifier => {
-                        let identifier = self.current_token.get_token_str_raw_value().unwrap();
-                        self.advance();
-                        if self.eat(TokenKind::Dot) && self.is_kind(TokenKind::Identifier) {
-                            let field = self.current_token.get_token_str_raw_value();
-                            FieldValue::ObjRef(
-                                self.find_nom_by_id_or_create(identifier),
-                                field.unwrap(),
-                            )
-                        } else {
-                            panic!("Identifier not found for non reference.");
-                        }
-                    }
+    /// Parses space separated values up to the end of the line.
+    fn parse_field_value(&mut self) -> Result<FieldValue> {
+        let value = self.parse_values()?;
+
+        if !(self.eat(TokenKind::Newline) || self.eat(TokenKind::Eof)) {
+            panic!("Invalid token : {:?}", self.current_kind());
+        }
 
-                    TokenKind::Litteral => {
-                        FieldValue::Litteral(self.current_token.get_token_str_raw_value().unwrap())
+        Ok(value)
+    }
+
+    /// Parses space separated values up to a token that cannot start one,
+    /// which is left as the current token.
+    fn parse_values(&mut self) -> Result<FieldValue> {
+        let mut value_vec = Vec::new();
+
+        loop {
+            self.eat(TokenKind::Space);
+            if !matches!(
+                self.current_kind(),
+                TokenKind::Dot
+                    | TokenKind::Identifier
+                    | TokenKind::Litteral
+                    | TokenKind::Interpolated
+                    | TokenKind::Number
+                    | TokenKind::At
+                    | TokenKind::Minus
+                    | TokenKind::LParen
+            ) || self.is_keyword("then")
+                || self.is_keyword("else")
+            {
+                break;
+            }
+            value_vec.push(self.parse_expression(0)?);
+        }
+
+        if value_vec.len() == 1 {
+            Ok(value_vec.pop().unwrap())
+        } else if value_vec.len() > 1 {
+            Ok(FieldValue::Vec(value_vec))
+        } else {
+            panic!(
+                "{}: Value cannot be empty, found {:?}.",
+                self.current_token.span,
+                self.current_kind()
+            );
+        }
+    }
+
+    /// Parses operands joined by operators binding at least as tightly as
+    /// `min_precedence`, operators of equal precedence grouping to the left.
+    fn parse_expression(&mut self, min_precedence: u8) -> Result<FieldValue> {
+        let mut left = self.parse_operand()?;
+
+        loop {
+            self.eat(TokenKind::Space);
+            let operator = match self.current_kind() {
+                TokenKind::Minus => Operator::Sub,
+                TokenKind::Operator => {
+                    let symbol = self.current_token.value.unwrap();
+                    Operator::from_symbol(symbol)
+                        .unwrap_or_else(|| panic!("Unknown operator : {}", symbol))
+                }
+                _ => break,
+            };
+
+            if operator.precedence() < min_precedence {
+                break;
+            }
+
+            self.advance();
+            self.eat(TokenKind::Space);
+            let right = self.parse_expression(operator.precedence() + 1)?;
+            left = FieldValue::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    /// Parses a single value, a negation or a parenthesized group, and moves
+    /// past it.
+    fn parse_operand(&mut self) -> Result<FieldValue> {
+        let value = match self.current_token.kind {
+            TokenKind::Minus => {
+                self.advance();
+                return Ok(FieldValue::Neg(Box::new(self.parse_operand()?)));
+            }
+
+            TokenKind::LParen => {
+                let span = self.current_token.span;
+                self.advance();
+                let value = self.parse_values()?;
+                if !self.eat(TokenKind::RParen) {
+                    panic!("{}: Unclosed parenthesis.", span);
+                }
+                return Ok(value);
+            }
+
+            TokenKind::Dot => {
+                self.advance();
+                if self.is_name() {
+                    FieldValue::FieldReference(self.intern_current())
+                } else {
+                    panic!("Token must be an identifier.");
+                }
+            }
+
+            TokenKind::Identifier => {
+                let name = self.current_token.get_token_str_raw_value().unwrap();
+                let span = self.current_token.span;
+                let identifier = self.intern_current();
+                self.advance();
+                if self.is_kind(TokenKind::LParen) {
+                    self.parse_call(name, span)?
+                } else if self.eat(TokenKind::Dot) && self.is_name() {
+                    let field = self.intern_current();
+                    match self.param_position(identifier) {
+                        Some(position) => {
+                            FieldValue::ParamRef(self.current.unwrap(), position, field)
+                        }
+                        None => {
+                            FieldValue::ObjRef(self.find_nom_by_id_or_create(identifier), field)
+                        }
                     }
+                } else if name == "if" {
+                    return self.parse_conditional(span);
+                } else if name == "true" || name == "false" {
+                    return Ok(FieldValue::Bool(name == "true"));
+                } else if name == "is" && self.eat(TokenKind::Space) && self.is_name() {
+                    let non = self.intern_current();
+                    FieldValue::Is(self.find_nom_by_id_or_create(non))
+                } else {
+                    panic!("Identifier not found for non reference.");
+                }
+            }
 
-                    TokenKind::At => FieldValue::FieldReference("id".to_string()),
+            TokenKind::Litteral => {
+                let litteral = self.current_token.get_token_str_raw_value().unwrap();
+                FieldValue::Litteral(litteral.to_string())
+            }
 
-                    token => panic!("Invalid token : {:?}", token),
-                };
-                value_vec.push(value);
+            TokenKind::Number => {
+                // an id made of digits only when followed by a field, though
+                // `2020.2021` still reads as a float
+                let number = self.current_token.value.unwrap();
+                let is_name = self.is_name();
                 self.advance();
+                if !(is_name && self.eat(TokenKind::Dot)) {
+                    return Ok(FieldValue::Number(number.to_string()));
+                } else if !self.is_name() {
+                    panic!("Identifier not found for non reference.");
+                }
+
+                let identifier = self.interner.intern(number);
+                let field = self.intern_current();
+                FieldValue::ObjRef(self.find_nom_by_id_or_create(identifier), field)
+            }
+
+            TokenKind::Interpolated => self.parse_interpolated()?,
+
+            TokenKind::At => FieldValue::FieldReference(Symbol::ID),
+
+            token => panic!("{}: Invalid token : {:?}", self.current_token.span, token),
+        };
+
+        self.advance();
+        Ok(value)
+    }
+
+    /// Parses `if <cond> then <values> else <values>` after the `if` keyword
+    /// found at `span`, the else branch running up to the end of the enclosing
+    /// values.
+    fn parse_conditional(&mut self, span: Span) -> Result<FieldValue> {
+        let condition = self.parse_values()?;
+        if !self.is_keyword("then") {
+            panic!("{}: 'then' required after the condition.", span);
+        }
+
+        self.advance();
+        let then = self.parse_values()?;
+        if !self.is_keyword("else") {
+            panic!("{}: 'else' required after 'then'.", span);
+        }
+
+        self.advance();
+        let otherwise = self.parse_values()?;
+
+        Ok(FieldValue::If(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Returns whether the current token can be an id or a field name, which
+    /// the lexer reads as a number when made of digits only.
+    fn is_name(&self) -> bool {
+        match self.current_kind() {
+            TokenKind::Identifier => true,
+            TokenKind::Number => !self.current_token.value.unwrap().contains('.'),
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.is_kind(TokenKind::Identifier) && self.current_token.value == Some(keyword)
+    }
+
+    /// Parses the arguments of a call to `name` declared at `span`, from the
+    /// opening parenthesis up to the closing one which is left as the current
+    /// token.
+    fn parse_call(&mut self, name: &str, span: Span) -> Result<FieldValue> {
+        let function = Function::from_name(name).ok_or_else(|| NonError::UnknownFunction {
+            span,
+            name: name.to_string(),
+        })?;
+        let mut args = Vec::new();
+        self.advance();
+        self.eat(TokenKind::Space);
+
+        while !self.is_kind(TokenKind::RParen) {
+            args.push(self.parse_values()?);
+            self.eat(TokenKind::Space);
+
+            if self.eat(TokenKind::Comma) {
+                self.eat(TokenKind::Space);
+                if self.is_kind(TokenKind::RParen) {
+                    panic!(
+                        "{}: Argument required after comma in call to {}.",
+                        self.current_token.span, name
+                    );
+                }
+            } else if !self.is_kind(TokenKind::RParen) {
+                panic!(
+                    "{}: Comma or closing parenthesis required in call to {}.",
+                    self.current_token.span, name
+                );
             }
         }
 
-        let value = if value_vec.len() == 1 {
-            value_vec.pop().unwrap()
-        } else if value_vec.len() > 1 {
-            FieldValue::Vec(value_vec)
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            let expected = if min == max {
+                min.to_string()
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return Err(NonError::FunctionArity {
+                span,
+                function: name.to_string(),
+                expected,
+                found: args.len(),
+            });
+        }
+
+        Ok(FieldValue::Call(function, args))
+    }
+
+    /// Splits an interpolated string into litteral text and `{...}`
+    /// placeholders, `{{` and `}}` standing for single braces.
+    fn parse_interpolated(&mut self) -> Result<FieldValue> {
+        let content = self.current_token.get_token_str_raw_value().unwrap();
+        let span = self.current_token.span;
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = content.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek().is_some_and(|&(_, next)| next == c) => {
+                    text.push(c);
+                    chars.next();
+                }
+                '{' => {
+                    let end = placeholder_end(content, i)
+                        .unwrap_or_else(|| panic!("{}: Unclosed placeholder in string.", span));
+                    let position = Span {
+                        line: span.line,
+                        column: span.column + 1 + content[..=i].chars().count(),
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(FieldValue::Litteral(std::mem::take(&mut text)));
+                    }
+                    parts.push(self.parse_placeholder(&content[i + 1..end], position)?);
+
+                    while chars.next_if(|&(j, _)| j <= end).is_some() {}
+                }
+                '}' => panic!("{}: Unmatched '}}' in string, write '}}}}' instead.", span),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(FieldValue::Litteral(text));
+        }
+
+        Ok(FieldValue::Interpolated(parts))
+    }
+
+    /// Parses the values of a placeholder found at `position`, then goes back
+    /// to the enclosing source.
+    fn parse_placeholder(&mut self, source: &'a str, position: Span) -> Result<FieldValue> {
+        let lexer = std::mem::replace(&mut self.lexer, NonLexer::with_position(source, position));
+        let token = self.current_token;
+
+        self.advance();
+        let value = self.parse_values()?;
+        if !self.is_kind(TokenKind::Eof) {
+            panic!(
+                "{}: Invalid token in placeholder : {:?}",
+                self.current_token.span,
+                self.current_kind()
+            );
+        }
+
+        self.lexer = lexer;
+        self.current_token = token;
+        Ok(value)
+    }
+
+    fn parse_deletion(&mut self) -> Symbol {
+        let field_name = if self.is_name() {
+            self.intern_current()
         } else {
-            panic!("Field value cannot be empty.");
+            panic!("Deleted field name must be an identifier.");
         };
 
-        (field_name, value)
+        self.advance();
+        if !(self.eat(TokenKind::Newline) || self.eat(TokenKind::Eof)) {
+            panic!("Newline required after a deleted field.");
+        }
+
+        field_name
     }
 
-    fn skip_spaces(&mut self) {
-        loop {
+    fn parse_schema(&mut self) -> (Symbol, FieldSchema) {
+        let field_name = if self.is_name() {
+            self.intern_current()
+        } else {
+            panic!("Schema field name must be an identifier.");
+        };
+
+        let mut schema = FieldSchema::default();
+        let mut keyword = None;
+        // keyword still waiting for its first value
+        let mut expecting = None;
+        self.advance();
+        while !(self.eat(TokenKind::Newline) || self.eat(TokenKind::Eof)) {
+            let span = self.current_token.span;
             if !self.eat(TokenKind::Space) {
-                break;
+                panic!(
+                    "{}: Space required before {:?} in schema.",
+                    span,
+                    self.current_kind()
+                );
+            }
+
+            if matches!(self.current_kind(), TokenKind::Newline | TokenKind::Eof) {
+                continue;
+            }
+
+            let span = self.current_token.span;
+            let value = self.current_token.get_token_str_raw_value();
+            match (self.current_token.kind, keyword) {
+                (TokenKind::Identifier, Some("type")) => {
+                    let value = value.unwrap();
+                    schema.value_type = Some(
+                        ValueType::from_name(value)
+                            .unwrap_or_else(|| panic!("{}: Unknown type : {}", span, value)),
+                    );
+                    keyword = None;
+                    expecting = None;
+                }
+                (TokenKind::Identifier, _) => {
+                    if let Some((word, span)) = expecting {
+                        panic!("{}: Schema constraint '{}' needs a value.", span, word);
+                    }
+
+                    match value.unwrap() {
+                        "required" => {
+                            schema.required = true;
+                            keyword = None;
+                        }
+                        "hidden" => {
+                            schema.hidden = true;
+                            keyword = None;
+                        }
+                        word @ ("type" | "pattern" | "in") => {
+                            keyword = Some(word);
+                            expecting = Some((word, span));
+                        }
+                        word => panic!("{}: Unknown schema constraint : {}", span, word),
+                    }
+                }
+                (TokenKind::Litteral, Some("pattern")) => {
+                    schema.pattern = value.map(str::to_string);
+                    keyword = None;
+                    expecting = None;
+                }
+                (TokenKind::Litteral, Some("in")) => {
+                    schema.values.push(value.unwrap().to_string());
+                    expecting = None;
+                }
+                (token, _) => panic!("{}: Invalid token in schema : {:?}", span, token),
             }
+            self.advance();
+        }
+
+        if let Some((word, span)) = expecting {
+            panic!("{}: Schema constraint '{}' needs a value.", span, word);
         }
+
+        (field_name, schema)
     }
 
     fn skip_newlines(&mut self) {
@@ -188,3 +718,368 @@ impl<'a> NonParser<'a> {
         self.current_token = self.lexer.read_next_token().unwrap_or_default();
     }
 }
+
+/// Byte position of the `}` closing the placeholder opened at `start`,
+/// skipping the ones inside litterals.
+fn placeholder_end(content: &str, start: usize) -> Option<usize> {
+    let mut quoted = false;
+
+    for (position, c) in content[start..].char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '}' if !quoted => return Some(start + position),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nds::NonDefs;
+
+    fn parse(source: &str) -> NonParser<'_> {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+        parser
+    }
+
+    fn schema(parser: &NonParser, field: &str) -> FieldSchema {
+        let field = parser.interner.get(field).unwrap();
+        FieldSchema {
+            span: Span::default(),
+            ..parser.nons[0].schema()[&field].clone()
+        }
+    }
+
+    #[test]
+    fn schema_lines_round_trip() {
+        let source = "student:\n!login required type string pattern '[a-z]+' in 'a' 'b' hidden\n";
+        let parsed = schema(&parse(source), "login");
+
+        let written = format!("student:\n!login{}\n", parsed);
+        assert_eq!(schema(&parse(&written), "login"), parsed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Space required")]
+    fn schema_rejects_unspaced_tokens() {
+        parse("student:\n!login required pattern'[a-z]+'\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid token in schema")]
+    fn schema_rejects_unexpected_tokens() {
+        parse("student:\n!x :\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "needs a value")]
+    fn schema_rejects_constraints_without_value() {
+        parse("student:\n!login required type\n");
+    }
+
+    #[test]
+    fn assignments_reject_invalid_values() {
+        for value in ["", " ", "'a' 'b", "'a' )", "'a'\n'b'"] {
+            let mut parser = parse("alice:\n.login 'x'\n");
+            assert!(matches!(
+                parser.parse_assignment("alice", "login", value, false),
+                Err(NonError::InvalidFieldValue(_))
+            ));
+        }
+
+        let mut parser = parse("alice:\n.login 'x'\n");
+        parser
+            .parse_assignment("alice", "login", "'a' 'b' ", false)
+            .unwrap();
+    }
+
+    #[test]
+    fn calls_are_checked() {
+        let mut parser = NonParser::new(NonLexer::new("a:\n.x nope('a')\n"));
+        assert!(matches!(
+            parser.parse(),
+            Err(NonError::UnknownFunction { .. })
+        ));
+
+        let mut parser = NonParser::new(NonLexer::new("a:\n.x lower('a', 'b')\n"));
+        assert!(matches!(
+            parser.parse(),
+            Err(NonError::FunctionArity { found: 2, .. })
+        ));
+
+        let mut parser = NonParser::new(NonLexer::new("a:\n.x lower()\n"));
+        assert!(matches!(
+            parser.parse(),
+            Err(NonError::FunctionArity { found: 0, .. })
+        ));
+    }
+
+    fn interpolated(source: &str) -> FieldValue {
+        let source = format!("a:\n.x {}\n", source);
+        let parser = parse(&source);
+        let x = parser.interner.get("x").unwrap();
+        parser.nons[0].own_fields()[&x].clone()
+    }
+
+    #[test]
+    fn interpolated_braces_are_escaped() {
+        assert_eq!(
+            interpolated("\"{{a}} {@}}}\""),
+            FieldValue::Interpolated(vec![
+                FieldValue::Litteral("{a} ".to_string()),
+                FieldValue::FieldReference(Symbol::ID),
+                FieldValue::Litteral("}".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn placeholders_skip_quoted_braces() {
+        let FieldValue::Interpolated(parts) = interpolated("\"{replace(.x, '}', 'y')}!\"") else {
+            panic!("Expected an interpolated value.");
+        };
+
+        assert!(matches!(parts[0], FieldValue::Call(Function::Replace, _)));
+        assert_eq!(parts[1], FieldValue::Litteral("!".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unmatched '}'")]
+    fn interpolated_rejects_single_closing_braces() {
+        interpolated("\"a } b\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unclosed placeholder")]
+    fn interpolated_rejects_unclosed_placeholders() {
+        interpolated("\"{.x '}\"");
+    }
+
+    #[test]
+    fn digit_ids_are_names() {
+        let parser = parse("2020:\n.2021 'a'\n\nb: 2020\n.x 2020.y\n.y 2020\n.z .2021\n");
+        let symbol = |name| parser.interner.get(name).unwrap();
+        let fields = parser.nons[1].own_fields();
+
+        assert_eq!(parser.nons[1].parents, vec![0]);
+        assert_eq!(fields[&symbol("x")], FieldValue::ObjRef(0, symbol("y")));
+        assert_eq!(fields[&symbol("y")], FieldValue::Number("2020".to_string()));
+        assert_eq!(
+            fields[&symbol("z")],
+            FieldValue::FieldReference(symbol("2021"))
+        );
+    }
+
+    #[test]
+    fn undeclared_ids_are_unknown() {
+        let mut parser = NonParser::new(NonLexer::new("a: p\n.x nope.y\n"));
+        assert!(matches!(parser.parse(), Err(NonError::UnknownNon(id)) if id == "p"));
+    }
+
+    fn build(source: &str) -> Result<NonDefs> {
+        let parser = parse(source);
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+    }
+
+    #[test]
+    fn template_arguments_are_counted() {
+        let arity = |source| match build(source) {
+            Err(NonError::TemplateArity {
+                expected, found, ..
+            }) => Some((expected, found)),
+            _ => None,
+        };
+
+        assert_eq!(arity("u:\n\na: u(u)\n"), Some((0, 1)));
+        assert_eq!(arity("u:\n\nt(x):\n.y x.z\n\na: t(u, u)\n"), Some((1, 2)));
+        assert!(build("u:\n.z 'z'\n\nt(x):\n.y x.z\n\na: t(u)\n").is_ok());
+    }
+
+    const BASE: &str = "univ:
+.domain 'a.tld'
+
+staff:
+.shell 'bash'
+
+student:
+.login @
+.mail .login '@' univ.domain
+
+alice: student
+";
+
+    fn overlay(overlay: &'static str, allow_new: bool) -> Result<NonDefs> {
+        let mut parser = NonParser::new(NonLexer::new(BASE));
+        parser.parse()?;
+        parser.parse_overlay(NonLexer::new(overlay), allow_new)?;
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+    }
+
+    #[test]
+    fn overlays_override_fields_and_append_parents() {
+        let defs = overlay(
+            "univ:\n.domain 'prod.tld'\n\nalice: staff\n.nick 'al'\n",
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(defs.get("alice", "mail").unwrap(), "alice@prod.tld");
+        assert_eq!(defs.get("alice", "shell").unwrap(), "bash");
+        assert_eq!(defs.get("alice", "nick").unwrap(), "al");
+
+        let alice = defs.at("alice").unwrap();
+        let parents = alice
+            .parents
+            .iter()
+            .map(|&parent| defs.id_of(parent))
+            .collect::<Vec<_>>();
+        assert_eq!(parents, ["student", "staff"]);
+    }
+
+    #[test]
+    fn overlays_delete_fields() {
+        let defs = overlay("student:\n-mail\n", false).unwrap();
+
+        assert_eq!(defs.get("alice", "login").unwrap(), "alice");
+        assert!(matches!(
+            defs.get("alice", "mail"),
+            Err(NonError::UnknownField { .. })
+        ));
+    }
+
+    #[test]
+    fn overlays_need_known_ids() {
+        assert!(matches!(
+            overlay("bob: student\n", false),
+            Err(NonError::UnknownNon(id)) if id == "bob"
+        ));
+        assert!(matches!(
+            overlay("alice:\n.site mit.domain\n", false),
+            Err(NonError::UnknownNon(id)) if id == "mit"
+        ));
+
+        let defs = overlay("bob: student\n", true).unwrap();
+        assert_eq!(defs.get("bob", "mail").unwrap(), "bob@a.tld");
+        assert!(matches!(
+            overlay("alice:\n.site mit.domain\n", true),
+            Err(NonError::UnknownNon(id)) if id == "mit"
+        ));
+    }
+
+    fn set(id: &str, field: &str, value: &'static str, allow_new: bool) -> Result<NonDefs> {
+        let mut parser = NonParser::new(NonLexer::new(BASE));
+        parser.parse()?;
+        parser.parse_assignment(id, field, value, allow_new)?;
+
+        NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+    }
+
+    #[test]
+    fn assignments_apply_before_resolution() {
+        let defs = set("univ", "domain", "'test.tld'", false).unwrap();
+        assert_eq!(defs.get("alice", "mail").unwrap(), "alice@test.tld");
+
+        let defs = set("alice", "login", "'al' @", false).unwrap();
+        assert_eq!(defs.get("alice", "mail").unwrap(), "alalice@a.tld");
+
+        let defs = set("student", "mail", ".login '@' staff.shell", false).unwrap();
+        assert_eq!(defs.get("alice", "mail").unwrap(), "alice@bash");
+    }
+
+    #[test]
+    fn assignments_need_known_fields() {
+        assert!(matches!(
+            set("bob", "login", "'bob'", false),
+            Err(NonError::UnknownNon(id)) if id == "bob"
+        ));
+        assert!(matches!(
+            set("alice", "nick", "'al'", false),
+            Err(NonError::UnknownField { field, .. }) if field == "nick"
+        ));
+        assert!(matches!(
+            set("alice", "mail", "mit.domain", true),
+            Err(NonError::UnknownNon(id)) if id == "mit"
+        ));
+
+        let defs = set("alice", "nick", "'al'", true).unwrap();
+        assert_eq!(defs.get("alice", "nick").unwrap(), "al");
+        let defs = set("bob", "login", "'bob'", true).unwrap();
+        assert_eq!(defs.get("bob", "login").unwrap(), "bob");
+    }
+
+    const CONDITIONS: &str = "person:
+.login @
+.shell if is staff then '/bin/bash' else '/usr/sbin/nologin'
+.home if .login == 'root' then '/root' else '/home/' .login
+
+staff: person
+
+alice: person
+
+bob: staff
+
+root: staff
+.title (if true then .missing else 'admin') ' ' .login
+.level if .login == 'x' then 1 else if is bob then 2 else 3
+.broken if .login then 'a' else 'b'
+";
+
+    #[test]
+    fn conditions_pick_a_branch() {
+        let defs = build(CONDITIONS).unwrap();
+
+        assert_eq!(defs.get("alice", "shell").unwrap(), "/usr/sbin/nologin");
+        assert_eq!(defs.get("bob", "shell").unwrap(), "/bin/bash");
+        assert_eq!(defs.get("alice", "home").unwrap(), "/home/alice");
+        assert_eq!(defs.get("root", "home").unwrap(), "/root");
+        assert_eq!(defs.get("root", "level").unwrap(), "3");
+    }
+
+    #[test]
+    fn conditions_only_resolve_the_taken_branch() {
+        let defs = build(CONDITIONS).unwrap();
+
+        assert!(matches!(
+            defs.get("root", "title"),
+            Err(NonError::UnknownField { field, .. }) if field == "missing"
+        ));
+        assert!(matches!(
+            defs.get("root", "broken"),
+            Err(NonError::NotABoolean(value)) if value == "root"
+        ));
+
+        let defs = build("a:\n.x if false then .missing else 'ok'\n").unwrap();
+        assert_eq!(defs.get("a", "x").unwrap(), "ok");
+    }
+
+    #[test]
+    fn conditions_are_written_back() {
+        let defs = build(CONDITIONS).unwrap();
+        let root = defs.at("root").unwrap();
+        let value =
+            |field| defs.value_to_string(&root.own_fields()[&defs.interner().get(field).unwrap()]);
+
+        assert_eq!(
+            value("title"),
+            "(if true then .missing else 'admin') ' ' .login"
+        );
+        assert_eq!(
+            value("level"),
+            "if .login == 'x' then 1 else if is bob then 2 else 3"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, fmt::Display};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    error::{NonError, Result},
+    interner::Symbol,
+    nds::NonDefs,
+    non::NonId,
+    token::Span,
+};
+
+/// Type a resolved field value must parse as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ValueType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl ValueType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    /// Name of the matching JSON Schema type.
+    pub fn json_type(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Int => "integer",
+            Self::Float => "number",
+            Self::Bool => "boolean",
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Self::String => true,
+            Self::Int => value.parse::<i64>().is_ok(),
+            Self::Float => value.parse::<f64>().is_ok(),
+            Self::Bool => value.parse::<bool>().is_ok(),
+        }
+    }
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String => f.write_str("string"),
+            Self::Int => f.write_str("int"),
+            Self::Float => f.write_str("float"),
+            Self::Bool => f.write_str("bool"),
+        }
+    }
+}
+
+/// Converts a resolved value to JSON, as a scalar of `value_type` when it
+/// parses as one and as a string otherwise. Infinite and NaN floats stay
+/// strings, as neither JSON nor TOML can write them as numbers.
+pub fn json_value(value_type: Option<ValueType>, value: &str) -> serde_json::Value {
+    let typed = match value_type {
+        Some(ValueType::Int) => value.parse::<i64>().ok().map(serde_json::Value::from),
+        Some(ValueType::Float) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(serde_json::Value::from),
+        Some(ValueType::Bool) => value.parse::<bool>().ok().map(serde_json::Value::from),
+        Some(ValueType::String) | None => None,
+    };
+
+    typed.unwrap_or_else(|| serde_json::Value::from(value))
+}
+
+/// Constraints declared on a field with a `!field ...` line. A `hidden`
+/// field can still be referenced but is left out of exports.
+///
+/// ```text
+/// student:
+/// !login required type string pattern '[a-z]+' hidden
+/// !level in 'L1' 'L2' 'L3'
+/// ```
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct FieldSchema {
+    pub required: bool,
+    pub value_type: Option<ValueType>,
+    pub pattern: Option<String>,
+    pub values: Vec<String>,
+    pub hidden: bool,
+    pub span: Span,
+}
+
+impl FieldSchema {
+    /// Tightens `self` with the constraints of a more specific declaration.
+    pub fn merge(&mut self, other: &FieldSchema) {
+        self.required |= other.required;
+        self.hidden |= other.hidden;
+
+        if other.value_type.is_some() {
+            self.value_type = other.value_type;
+        }
+
+        if other.pattern.is_some() {
+            self.pattern = other.pattern.clone();
+        }
+
+        if !other.values.is_empty() {
+            self.values = other.values.clone();
+        }
+
+        self.span = other.span;
+    }
+}
+
+/// Writes the constraints back as they appear after the field name of a
+/// `!field` line.
+impl Display for FieldSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.required {
+            f.write_str(" required")?;
+        }
+
+        if let Some(value_type) = self.value_type {
+            write!(f, " type {}", value_type)?;
+        }
+
+        if let Some(pattern) = &self.pattern {
+            write!(f, " pattern '{}'", pattern)?;
+        }
+
+        if !self.values.is_empty() {
+            f.write_str(" in")?;
+            for value in &self.values {
+                write!(f, " '{}'", value)?;
+            }
+        }
+
+        if self.hidden {
+            f.write_str(" hidden")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A field of a concrete non breaking its schema.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub non: String,
+    pub field: String,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}.{}: {}",
+            self.span, self.non, self.field, self.message
+        )
+    }
+}
+
+/// Validates every concrete non against the schemas inherited from its
+/// ancestors.
+pub fn check(defs: &NonDefs) -> Result<Vec<Violation>> {
+    let mut patterns = HashMap::new();
+    let mut violations = Vec::new();
+
+    for index in defs.concrete() {
+        let mut schema = defs.schema_at(index).into_iter().collect::<Vec<_>>();
+        schema.sort_by_key(|(_, field_schema)| field_schema.span.line);
+
+        for (field, field_schema) in schema {
+            let violation = |span: Span, message: String| Violation {
+                non: defs.id_of(index).to_string(),
+                field: defs.name(field).to_string(),
+                span,
+                message,
+            };
+
+            if !defs.fields_at(index).contains_key(&field) {
+                if field_schema.required {
+                    let span = defs.non(index).span;
+                    violations.push(violation(span, "required field is missing".to_string()));
+                }
+                continue;
+            }
+
+            let span = field_span(defs, index, field).unwrap_or(defs.non(index).span);
+
+            let value = match defs.get_at(index, field) {
+                Ok(value) => value,
+                Err(err) => {
+                    violations.push(violation(span, err.to_string()));
+                    continue;
+                }
+            };
+
+            if let Some(value_type) = field_schema.value_type
+                && !value_type.matches(&value)
+            {
+                let message = format!("expected {}, found '{}'", value_type, value);
+                violations.push(violation(span, message));
+            }
+
+            if let Some(pattern) = &field_schema.pattern {
+                if !patterns.contains_key(pattern) {
+                    let regex = Regex::new(&format!("^(?:{})$", pattern))
+                        .map_err(|_| NonError::InvalidPattern(pattern.clone()))?;
+                    patterns.insert(pattern.clone(), regex);
+                }
+
+                if !patterns[pattern].is_match(&value) {
+                    let message = format!("'{}' does not match pattern '{}'", value, pattern);
+                    violations.push(violation(span, message));
+                }
+            }
+
+            if !field_schema.values.is_empty() && !field_schema.values.contains(&value) {
+                let allowed = field_schema
+                    .values
+                    .iter()
+                    .map(|v| format!("'{}'", v))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let message = format!("'{}' is not one of {}", value, allowed);
+                violations.push(violation(span, message));
+            }
+        }
+    }
+
+    Ok(violations)
+}
+
+/// Span of the declaration providing the value of `field` for the non at
+/// `index`, which may live in one of its ancestors.
+fn field_span(defs: &NonDefs, index: NonId, field: Symbol) -> Option<Span> {
+    let origin = defs.field_origin(index, field)?;
+    defs.non(origin).field_span(field)
+}
@@ -1,19 +1,28 @@
 use std::fmt::Display;
 
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::error::{NonError, Result};
 
-pub type TokenValue = Option<String>;
+pub type TokenValue<'a> = Option<&'a str>;
 
 #[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenKind {
     Identifier,
     Litteral,
+    Interpolated,
+    Number,
+    Operator,
     Space,
     Dot,
     Colon,
     At,
+    Bang,
+    Minus,
+    LParen,
+    RParen,
+    Comma,
     Newline,
     Eof,
 }
@@ -23,39 +32,80 @@ impl Display for TokenKind {
         match self {
             Self::Identifier => f.write_str("Identifier"),
             Self::Litteral => f.write_str("Litteral"),
+            Self::Interpolated => f.write_str("Interpolated"),
+            Self::Number => f.write_str("Number"),
+            Self::Operator => f.write_str("Operator"),
             Self::Space => f.write_str("Space"),
             Self::Dot => f.write_str("Dot"),
             Self::Colon => f.write_str("Colon"),
             Self::At => f.write_str("At"),
+            Self::Bang => f.write_str("Bang"),
+            Self::Minus => f.write_str("Minus"),
+            Self::LParen => f.write_str("LParen"),
+            Self::RParen => f.write_str("RParen"),
+            Self::Comma => f.write_str("Comma"),
             Self::Newline => f.write_str("NewLine"),
             Self::Eof => f.write_str("EOF"),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Token {
+/// 1-based line and column of a token in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A token borrowing its value straight from the source text.
+#[derive(Debug, Clone, Copy)]
+pub struct Token<'a> {
     pub kind: TokenKind,
-    pub value: TokenValue,
+    pub value: TokenValue<'a>,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(kind: TokenKind, value: TokenValue) -> Self {
-        Self { kind, value }
+impl<'a> Token<'a> {
+    pub fn new(kind: TokenKind, value: TokenValue<'a>) -> Self {
+        Self {
+            kind,
+            value,
+            span: Span::default(),
+        }
     }
 
-    pub fn get_token_str_raw_value(&self) -> Option<String> {
-        if matches!(self.kind, TokenKind::Identifier | TokenKind::Litteral)
-            && let Some(value) = &self.value
-        {
-            return Some(value.to_owned());
+    pub fn get_token_str_raw_value(&self) -> Option<&'a str> {
+        if matches!(
+            self.kind,
+            TokenKind::Identifier | TokenKind::Litteral | TokenKind::Interpolated
+        ) {
+            return self.value;
         }
 
         None
     }
 
-    fn verify_token(kind: TokenKind, value: TokenValue) -> Result<()> {
-        if matches!(kind, TokenKind::Identifier | TokenKind::Litteral) {
+    fn verify_token(kind: TokenKind, value: TokenValue<'a>) -> Result<()> {
+        if matches!(
+            kind,
+            TokenKind::Identifier
+                | TokenKind::Litteral
+                | TokenKind::Interpolated
+                | TokenKind::Number
+                | TokenKind::Operator
+        ) {
             if value.is_some() {
                 return Ok(());
             } else {
@@ -67,16 +117,17 @@ impl Token {
     }
 }
 
-impl Default for Token {
+impl Default for Token<'_> {
     fn default() -> Self {
         Self {
             kind: TokenKind::Eof,
             value: None,
+            span: Span::default(),
         }
     }
 }
 
-impl From<TokenKind> for Token {
+impl From<TokenKind> for Token<'_> {
     fn from(token: TokenKind) -> Self {
         let _ = Token::verify_token(token, None);
         Token::new(token, None)
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use serde_json::json;
+
+use crate::{
+    codegen::{TypeField, type_fields, type_name},
+    error::Result,
+    nds::NonDefs,
+    non::{FieldValue, NonId},
+    schema::{ValueType, json_value},
+};
+
+/// TypeScript type of a field of an interface.
+#[derive(Debug, Clone, PartialEq)]
+struct FieldType {
+    base: &'static str,
+    /// Value shared by the non and all of its descendants, as a literal type.
+    literal: Option<String>,
+    optional: bool,
+}
+
+impl FieldType {
+    /// Whether a value of this type is a valid value of `other`.
+    fn is_assignable_to(&self, other: &FieldType) -> bool {
+        self.base == other.base
+            && (other.literal.is_none() || self.literal == other.literal)
+            && (other.optional || !self.optional)
+    }
+}
+
+/// Builds TypeScript declarations of the `fields` objects of the flat `json`
+/// output, one interface per non.
+///
+/// Fields whose litteral value no descendant overrides get a literal type.
+/// When `flat` is false, interfaces extend the ones of the parents of their
+/// non and only declare what these do not already declare the same way;
+/// inherited fields a non unsets or types differently are omitted from its
+/// parents with `Omit`.
+pub fn serialize(defs: &NonDefs, flat: bool) -> Result<String> {
+    let type_fields = type_fields(defs);
+
+    let interfaces = (0..defs.nons().len())
+        .map(|index| {
+            let fields = field_types(defs, &type_fields[index], index)?;
+            let mut extends = Vec::new();
+            let mut inherited = BTreeMap::new();
+
+            if !flat {
+                for &parent in &defs.non(index).parents {
+                    let mut omitted = Vec::new();
+
+                    for (name, parent_type) in field_types(defs, &type_fields[parent], parent)? {
+                        match fields.get(name) {
+                            Some(field_type) if field_type.is_assignable_to(&parent_type) => {
+                                inherited
+                                    .entry(name)
+                                    .or_insert(Vec::new())
+                                    .push(parent_type);
+                            }
+                            _ => omitted.push(json!(name).to_string()),
+                        }
+                    }
+
+                    let parent = type_name(defs.id_of(parent));
+                    if omitted.is_empty() {
+                        extends.push(parent);
+                    } else {
+                        extends.push(format!("Omit<{}, {}>", parent, omitted.join(" | ")));
+                    }
+                }
+            }
+
+            let mut lines = vec![format!("/** Fields of the `{}` non. */", defs.id_of(index))];
+            if extends.is_empty() {
+                lines.push(format!(
+                    "export interface {} {{",
+                    type_name(defs.id_of(index))
+                ));
+            } else {
+                lines.push(format!(
+                    "export interface {} extends {} {{",
+                    type_name(defs.id_of(index)),
+                    extends.join(", ")
+                ));
+            }
+
+            for (name, field_type) in &fields {
+                let inherited_as_is = inherited
+                    .get(name)
+                    .is_some_and(|types| types.iter().all(|parent_type| parent_type == field_type));
+                if inherited_as_is {
+                    continue;
+                }
+
+                lines.push(format!(
+                    "  {}{}: {};",
+                    property_name(name),
+                    if field_type.optional { "?" } else { "" },
+                    field_type.literal.as_deref().unwrap_or(field_type.base)
+                ));
+            }
+
+            lines.push("}".to_string());
+            Ok(lines.join("\n"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(interfaces.join("\n\n"))
+}
+
+fn field_types<'a>(
+    defs: &'a NonDefs,
+    type_fields: &BTreeMap<&'a str, TypeField>,
+    index: NonId,
+) -> Result<BTreeMap<&'a str, FieldType>> {
+    let values = defs.fields_at(index);
+
+    type_fields
+        .iter()
+        .map(|(&name, field)| {
+            let base = match field.value_type {
+                Some(ValueType::Int | ValueType::Float) => "number",
+                Some(ValueType::Bool) => "boolean",
+                Some(ValueType::String) | None => "string",
+            };
+
+            let value = defs
+                .interner()
+                .get(name)
+                .and_then(|symbol| values.get(&symbol));
+            let literal = match value {
+                Some(value) if field.shared && is_constant(value) => Some(
+                    json_value(field.value_type, &defs.resolve_field(index, value)?).to_string(),
+                ),
+                _ => None,
+            };
+
+            let field_type = FieldType {
+                base,
+                literal,
+                optional: !field.required,
+            };
+            Ok((name, field_type))
+        })
+        .collect()
+}
+
+/// Whether `value` resolves the same way whatever the non resolving it.
+fn is_constant(value: &FieldValue) -> bool {
+    match value {
+        FieldValue::Litteral(_) | FieldValue::Number(_) | FieldValue::Bool(_) => true,
+        FieldValue::Neg(operand) => is_constant(operand),
+        _ => false,
+    }
+}
+
+/// Writes `name` as a property name, quoted unless it is a valid identifier.
+fn property_name(name: &str) -> String {
+    let identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+
+    if identifier {
+        name.to_string()
+    } else {
+        json!(name).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::NonLexer, parser::NonParser};
+
+    fn typescript(source: &str, flat: bool) -> String {
+        let mut parser = NonParser::new(NonLexer::new(source));
+        parser.parse().unwrap();
+        let defs = NonDefs::builder()
+            .nons(parser.nons)
+            .interner(parser.interner)
+            .build()
+            .unwrap();
+
+        serialize(&defs, flat).unwrap()
+    }
+
+    #[test]
+    fn fields_are_typed_like_their_descendants() {
+        let output = typescript(
+            "person:\n.age 30\n.kind 'person'\n\nalice: person\n.age 15 * 2\n",
+            true,
+        );
+
+        assert!(
+            output.contains("export interface Person {\n  age: number;\n  kind: \"person\";\n}")
+        );
+        assert!(
+            output.contains("export interface Alice {\n  age: number;\n  kind: \"person\";\n}")
+        );
+    }
+
+    #[test]
+    fn overridden_values_are_not_literal_types() {
+        let output = typescript(
+            "person:\n.kind 'person'\n\nalice: person\n.kind 'student'\n",
+            false,
+        );
+
+        assert!(output.contains("export interface Person {\n  kind: string;\n}"));
+        assert!(
+            output.contains("export interface Alice extends Person {\n  kind: \"student\";\n}")
+        );
+    }
+}